@@ -0,0 +1,168 @@
+use crossterm::event::KeyCode;
+
+use crate::app::AuthMode;
+use crate::chat_tui::ChatMessage;
+use crate::config::Config;
+use crate::drafts::Draft;
+use crate::glyphs::GlyphSet;
+use crate::keymap::KeyPress;
+use crate::theme::Theme;
+
+// stuff the network tasks send back to the ui
+#[derive(Debug)]
+pub enum AppEvent {
+    AuthFinished(Result<String, String>),
+    ChatMessage(ChatMessage),
+    CustomThemeLoaded(Option<Theme>),
+    EditorFinished(Result<String, String>),
+    Error(String),
+}
+
+// everything that can happen to the app, input gets turned into these by the map_event
+// functions and update() is the only thing that touches state
+#[derive(Debug)]
+pub enum Action {
+    Quit,
+    Tick,
+    Resize,
+    Net(AppEvent),
+
+    // auth form
+    FocusNext,
+    FocusPrev,
+    SetAuthMode(AuthMode),
+    ToggleAuthMode,
+    Submit,
+    PrevIcon,
+    NextIcon,
+
+    // home
+    Continue,
+
+    // text editing, goes to whichever input has focus
+    InsertChar(char),
+    Paste(String),
+    InsertNewline,
+    Backspace,
+    Delete,
+    CursorLeft,
+    CursorRight,
+    CursorUp,
+    CursorDown,
+    CursorHome,
+    CursorEnd,
+    CursorWordLeft,
+    CursorWordRight,
+    DeleteWordBack,
+    DeleteWordForward,
+    KillToStart,
+    KillToEnd,
+    Yank,
+    YankPop,
+    Transpose,
+    Undo,
+    Redo,
+    HistorySearch,
+    CancelSearch,
+    // the first key of a chord, the next key finishes it
+    ChordPrefix(KeyPress),
+    // a key the keymap ate without doing anything, with why if it's worth saying
+    Unbound(String),
+    Complete,
+    CompletePrev,
+    VimKey(KeyCode),
+    EditExternally,
+
+    // chat
+    SendMessage,
+    ScrollUp,
+    ScrollDown,
+    // the mouse wheel, lines, negative is up
+    ScrollChat(isize),
+    CloseProfile,
+
+    // mouse
+    Click(ClickTarget),
+    ToggleMouse,
+
+    // popup pickers
+    OpenThemePicker,
+    OpenGlyphPicker,
+    // with whatever to search for already typed in
+    OpenEmojiPicker(String),
+    PickGlyphs(GlyphSet),
+    PickerPrev,
+    PickerNext,
+    PickerConfirm,
+    PickerCancel,
+    PickerCategory(bool),
+    PickerType(char),
+    PickerErase,
+    OpenDiagnostics,
+    CloseDiagnostics,
+    OpenHelp,
+    CloseHelp,
+    HelpType(char),
+    HelpErase,
+    // lines, negative is up
+    HelpScroll(isize),
+    ResolvePaste(PasteChoice),
+}
+
+// the things a page marks clickable while drawing, see App::click_areas
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClickTarget {
+    // a login field and the column clicked, counting from its left border
+    Input(usize, u16),
+    SubmitButton,
+    PrevIcon,
+    NextIcon,
+    ToggleAuthMode,
+    // row and column inside the composer's text
+    Composer(u16, u16),
+    // a name on a message header
+    User(String),
+}
+
+// what to do with a paste too big to just drop in the composer
+#[derive(Debug, Clone, Copy)]
+pub enum PasteChoice {
+    Insert,
+    CodeBlock,
+    Cancel,
+}
+
+// side effects update() wants done, main runs them so the reducer never does io
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    Login {
+        username: String,
+        password: String,
+    },
+    Register {
+        username: String,
+        password: String,
+        icon: String,
+    },
+    // drops whatever connection there is first
+    ConnectChat {
+        token: String,
+    },
+    Disconnect,
+    SendChat(String),
+    OpenUrl(String),
+    SaveConfig(Config),
+    SaveGlyphs(GlyphSet),
+    EditExternally(String),
+    SaveRecentEmoji(Vec<String>),
+    SaveDraft {
+        server: String,
+        draft: Draft,
+    },
+    SaveHistory {
+        server: String,
+        entries: Vec<String>,
+    },
+    // false hands the mouse back to the terminal, for selecting text
+    SetMouseCapture(bool),
+}
@@ -1,6 +1,8 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+pub const API_BASE: &str = "http://back.reetui.hackclub.app";
+
 #[derive(Serialize)]
 pub struct RegisterInput {
     pub username: String,
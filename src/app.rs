@@ -1,21 +1,43 @@
-use std::time::Duration;
+use ratatui::layout::Rect;
+use std::collections::VecDeque;
 use std::time::Instant;
 
+use crate::action::{ClickTarget, Effect};
+use crate::background::{Background, Detection};
+use crate::chat_tui::{ChatMessage, MessageLayoutCache};
+use crate::colors::{self, ColorDepth};
+use crate::completion::Completion;
+use crate::config::{Config, Dirs};
+use crate::drafts::{self, Draft};
+use crate::editor::Editor;
+use crate::emoji;
+use crate::emoji_picker::EmojiPicker;
+use crate::glyph_picker::GlyphPicker;
+use crate::glyphs::{GlyphSet, Glyphs};
+use crate::help::Help;
+use crate::history::{self, History};
+use crate::keymap::{Context, KeyPress, Keymap};
+use crate::profile::Profile;
+use crate::theme::{self, Theme};
+use crate::theme_picker::ThemePicker;
+use crate::vim::Vim;
+
+pub const MAX_CHAT_HISTORY: usize = 50_000;
+
 pub enum Page {
     Auth,
     Home,
     Chat,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum AuthMode {
     Login,
     Register,
 }
 
 pub struct InputBox {
-    pub value: String,
-    pub cursor: usize,
+    pub editor: Editor,
     pub is_password: bool,
     pub label: String,
 }
@@ -23,17 +45,16 @@ pub struct InputBox {
 impl InputBox {
     pub fn new(label: &str, is_password: bool) -> Self {
         Self {
-            value: String::new(),
-            cursor: 0,
+            editor: Editor::default(),
             is_password,
             label: label.to_string(),
         }
     }
     pub fn display(&self) -> String {
         if self.is_password {
-            "bla".repeat(self.value.len())
+            "bla".repeat(self.editor.text().len())
         } else {
-            self.value.clone()
+            self.editor.text().to_string()
         }
     }
 }
@@ -47,26 +68,104 @@ pub struct App {
     pub error_time: Option<Instant>,
     pub token: Option<String>,
     pub is_loading: bool,
-    pub chat_messages: Vec<crate::chat_tui::ChatMessage>,
-    pub chat_input: String,
+    pub chat_messages: VecDeque<ChatMessage>,
+    pub message_cache: MessageLayoutCache,
+    pub next_message_id: u64,
+    pub next_link_number: usize,
+    pub composer: Editor,
+    // the unsent message as last saved, the composer is the live one
+    pub draft: Draft,
+    // when the composer last changed without the draft being saved
+    pub drafts_changed: Option<Instant>,
+    pub history: History,
+    pub pending_paste: Option<String>,
+    pub completion: Option<Completion>,
+    pub keymap: Keymap,
+    // the start of a chord, waiting for the rest of it
+    pub pending_keys: Vec<KeyPress>,
+    // only there when the config turns modal editing on
+    pub vim: Option<Vim>,
     pub should_quit: bool,
     pub icon_index: usize,
-    pub icons: Vec<&'static str>,
     pub current_icon: String,
-    pub chat_scroll: u16,
-    pub input_scroll: u16,
-    pub input_cursor: usize,
+    pub chat_scroll: usize,
     pub input_width: usize,
+    // first composer row on screen, moves only as far as it takes to keep the cursor in view
+    pub composer_scroll: usize,
     pub last_sent: Option<std::time::Instant>,
     pub auto_scroll: bool,
-    pub max_scroll: u16,
+    pub max_scroll: usize,
     pub cursor_tick_state: bool,
-    pub last_cursor_toggle: Instant,
+    pub config: Config,
+    // config.json was there but didn't load, saving would replace it with the defaults
+    pub config_broken: bool,
+    pub dirs: Dirs,
+    pub color_depth: ColorDepth,
+    pub theme: Theme,
+    pub theme_name: String,
+    pub theme_generation: u64,
+    pub custom_theme: Option<Theme>,
+    pub theme_picker: Option<ThemePicker>,
+    pub glyph_set: GlyphSet,
+    pub background: Detection,
+    pub show_diagnostics: bool,
+    pub help: Option<Help>,
+    pub glyph_picker: Option<GlyphPicker>,
+    pub emoji_picker: Option<EmojiPicker>,
+    // emoji names, newest first
+    pub recent_emoji: Vec<String>,
+    pub dirty: bool,
+    pub timestamp_refresh: Option<Instant>,
+    pub hyperlinks_supported: bool,
+    // the terminal took the kitty keyboard flags, so Shift+Enter can be told apart from Enter
+    pub keyboard_enhanced: bool,
+    pub hyperlinks: Vec<crate::chat_tui::Hyperlink>,
+    // what's clickable on the page as last drawn, later ones sit on top
+    pub click_areas: Vec<(Rect, ClickTarget)>,
+    pub mouse_captured: bool,
+    pub profile: Option<Profile>,
 }
 
 impl App {
     pub fn new() -> Self {
-        Self {
+        App::with_dirs(Dirs::standard())
+    }
+
+    // everything read from disk comes from under `dirs`, tests give it an empty one
+    pub fn with_dirs(dirs: Dirs) -> Self {
+        let mut errors = Vec::new();
+        let mut config_broken = false;
+        let config = Config::load(&dirs).unwrap_or_else(|e| {
+            errors.push(e);
+            config_broken = true;
+            Config::default()
+        });
+        let custom_theme = theme::load_custom(&dirs).unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        let server = history::server_key(crate::chat_tui::WS_URL);
+        let history = History::load(&dirs, server.clone()).unwrap_or_else(|e| {
+            errors.push(e);
+            History::new(server)
+        });
+        let draft = drafts::load(&dirs, &history.server).unwrap_or_else(|e| {
+            errors.push(e);
+            Draft::default()
+        });
+        let recent_emoji = emoji::load_recent(&dirs).unwrap_or_else(|e| {
+            errors.push(e);
+            Vec::new()
+        });
+        let keymap = Keymap::load(&dirs);
+        if !keymap.problems.is_empty() {
+            errors.push(format!(
+                "keybindings.toml: {} problem(s), see F12",
+                keymap.problems.len()
+            ));
+        }
+        let startup_error = (!errors.is_empty()).then(|| errors.join(", "));
+        let mut app = Self {
             page: Page::Auth,
             auth_mode: AuthMode::Register,
             input_boxes: vec![
@@ -75,74 +174,204 @@ impl App {
                 InputBox::new("Icon", false),
             ],
             focus: 0,
-            error: None,
-            error_time: None,
+            error_time: startup_error.as_ref().map(|_| Instant::now()),
+            error: startup_error,
             token: None,
             is_loading: false,
-            chat_input: String::new(),
-            chat_messages: Vec::new(),
+            composer: Editor::default(),
+            draft,
+            drafts_changed: None,
+            history,
+            pending_paste: None,
+            completion: None,
+            keymap,
+            pending_keys: Vec::new(),
+            vim: config.vim.then(Vim::new),
+            chat_messages: VecDeque::new(),
+            message_cache: MessageLayoutCache::default(),
+            next_message_id: 0,
+            next_link_number: 0,
             should_quit: false,
             icon_index: 0,
-            icons: vec![
-                "󰱨",
-                "󰱩",
-                "󰱫",
-                "󰄛",
-                "󰊖",
-                "󱃞",
-                "󰱬",
-                "󰱮",
-                "󰱯",
-                "󰱰",
-                "󰽌",
-                "󰱱",
-                "󰱲",
-                "󱈔",
-                "󰱸",
-                "󰇳",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "󱢯",
-                "",
-                "󰇟",
-                "󰞅",
-                "🤓👆",
-                "🗿",
-                "🧑‍💻",
-                "󰱴",
-                "󰇷",
-                "󱕼",
-                "󰇹",
-                "",
-                "",
-                "",
-                "",
-                "",
-                "󰇶",
-                "󰻟",
-                "",
-                "",
-                "󰣑",
-                "󰢮",
-                "",
-            ],
             current_icon: String::new(),
-            input_cursor: 0,
             chat_scroll: 0,
-            input_scroll: 0,
             input_width: 0,
+            composer_scroll: 0,
             last_sent: None,
             auto_scroll: true,
             max_scroll: 0,
             cursor_tick_state: true,
-            last_cursor_toggle: Instant::now(),
+            color_depth: colors::detect(config.color_depth),
+            config,
+            config_broken,
+            dirs,
+            theme: Theme::default(),
+            theme_name: "dark".to_string(),
+            theme_generation: 0,
+            custom_theme,
+            theme_picker: None,
+            glyph_set: GlyphSet::NerdFont,
+            background: Detection::default(),
+            show_diagnostics: false,
+            help: None,
+            glyph_picker: None,
+            emoji_picker: None,
+            recent_emoji,
+            dirty: true,
+            timestamp_refresh: None,
+            hyperlinks_supported: crate::chat_tui::supports_hyperlinks(),
+            hyperlinks: Vec::new(),
+            click_areas: Vec::new(),
+            mouse_captured: true,
+            profile: None,
+            keyboard_enhanced: false,
+        };
+        let name = app.configured_theme();
+        if let Err(e) = app.apply_theme(&name) {
+            app.error = Some(e);
+            app.error_time = Some(Instant::now());
+        }
+        let draft = app.draft.clone();
+        app.composer.restore(&draft.text, draft.cursor);
+        app
+    }
+}
+
+impl App {
+    // anything cached with the old colors is stale after this
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme.with_depth(self.color_depth);
+        self.theme_generation += 1;
+    }
+
+    pub fn glyphs(&self) -> &'static Glyphs {
+        self.glyph_set.glyphs()
+    }
+
+    pub fn icons(&self) -> &'static [&'static str] {
+        self.glyphs().icons
+    }
+
+    // cached message bodies have the old prefixes baked in, so this counts as a new look too
+    pub fn set_glyphs(&mut self, set: GlyphSet) {
+        self.glyph_set = set;
+        self.icon_index %= self.icons().len();
+        if !self.current_icon.is_empty() {
+            self.current_icon = self.icons()[self.icon_index].to_string();
+        }
+        self.theme_generation += 1;
+    }
+
+    // the config as it is now, unless the file on disk never loaded. then it's left alone
+    // until it's fixed, the change still holds for this session
+    pub fn save_config(&mut self) -> Vec<Effect> {
+        if self.config_broken {
+            self.error = Some("config.json didn't load, not saving over it".to_string());
+            self.error_time = Some(Instant::now());
+            return vec![];
+        }
+        vec![Effect::SaveConfig(self.config.clone())]
+    }
+
+    // the theme config.json asks for, a custom theme.json wins when nothing was picked and
+    // after that it goes by the terminal background
+    pub fn configured_theme(&self) -> String {
+        match &self.config.theme {
+            Some(name) => name.clone(),
+            None if self.custom_theme.is_some() => theme::CUSTOM.to_string(),
+            None if self.background.background() == Some(Background::Light) => "light".to_string(),
+            None => "dark".to_string(),
+        }
+    }
+
+    pub fn set_background(&mut self, detection: Detection) {
+        self.background = detection;
+        if self.config.theme.is_none() {
+            let name = self.configured_theme();
+            let _ = self.apply_theme(&name);
+        }
+    }
+
+    // switch to a preset or the custom theme by name, doesn't touch the config
+    pub fn apply_theme(&mut self, name: &str) -> Result<(), String> {
+        let theme = if name.eq_ignore_ascii_case(theme::CUSTOM) {
+            self.custom_theme
+                .clone()
+                .ok_or_else(|| "No custom theme.json in the config dir".to_string())?
+        } else {
+            theme::preset(name).ok_or_else(|| {
+                let names = theme::names(self.custom_theme.is_some()).join(", ");
+                format!("Unknown theme {name}, try one of: {names}")
+            })?
+        };
+        self.theme_name = name.to_lowercase();
+        self.set_theme(theme);
+        Ok(())
+    }
+
+    pub fn push_message(&mut self, mut msg: ChatMessage) {
+        msg.id = self.next_message_id;
+        self.next_message_id += 1;
+        msg.link_base = self.next_link_number;
+        self.next_link_number += crate::chat_tui::find_urls(&msg.content).len();
+
+        self.chat_messages.push_back(msg);
+        // the layout cache notices on the next frame
+        while self.chat_messages.len() > MAX_CHAT_HISTORY {
+            self.chat_messages.pop_front();
         }
     }
+
+    // keeps the draft in step with the composer, called after every action
+    pub fn sync_draft(&mut self) {
+        let draft = Draft {
+            text: self.composer.text().to_string(),
+            cursor: self.composer.cursor(),
+        };
+        // an empty composer is no draft, wherever its cursor is
+        if draft == self.draft || (draft.text.is_empty() && self.draft.text.is_empty()) {
+            return;
+        }
+        self.draft = draft;
+        self.drafts_changed.get_or_insert_with(Instant::now);
+    }
+
+    // something the client itself has to say, shows up in the chat but never gets sent
+    pub fn notice(&mut self, content: impl Into<String>) {
+        self.push_message(ChatMessage {
+            user: "system".to_string(),
+            content: content.into(),
+            system: true,
+            timestamp: Some(chrono::Local::now().timestamp()),
+            ..Default::default()
+        });
+        self.auto_scroll = true;
+    }
+
+    // the newline key worth telling people about, Shift+Enter only counts when the terminal
+    // can actually report it
+    pub fn newline_key(&self) -> Option<String> {
+        let keys = self.keymap.keys_for(&[Context::Chat], "newline");
+        let shift_enter = keys.iter().find(|k| k.as_str() == "shift+enter");
+        match shift_enter {
+            Some(key) if self.keyboard_enhanced => Some(key.clone()),
+            _ => keys.into_iter().find(|k| k.as_str() != "shift+enter"),
+        }
+    }
+
+    // what the composer would send, in characters, :shortcodes: already turned into emoji
+    pub fn message_length(&self) -> usize {
+        let text = self.composer.text().trim();
+        emoji::expand(crate::commands::unescape(text))
+            .chars()
+            .count()
+    }
+
+    pub fn clear_chat(&mut self) {
+        self.chat_messages.clear();
+        self.message_cache.clear();
+        self.hyperlinks.clear();
+        self.chat_scroll = 0;
+        self.auto_scroll = true;
+    }
 }
@@ -1,183 +1,216 @@
-use crate::api::{login, register};
-use crate::app::{App, AuthMode};
-use crate::mpsc::UnboundedSender;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crate::action::{Action, AppEvent, ClickTarget, Effect};
+use crate::app::{App, AuthMode, InputBox, Page};
+use crate::keymap::{self, Context};
+use crate::text_layout::{str_width, TextLayout};
+use crate::theme::Theme;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Paragraph},
+    widgets::{Block, Borders, Paragraph},
     Frame,
 };
-use serde::Deserialize;
 use std::time::Instant;
-use std::{fs, path::PathBuf};
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct Rgb(u8, u8, u8);
+// very unoptimized code, double function and stuff, still working on it
 
-#[derive(Deserialize, Debug)]
-pub struct Theme {
-    pub border: Rgb,
-    pub border_focus: Rgb,
-    pub button: Rgb,
-    pub button_focus: Rgb,
-    pub text: Rgb,
-}
+const ICON_INPUT_IDX: usize = 2;
 
-// very unoptimized code, double function and stuff, still working on it
+pub fn map_event(evt: Event, app: &App) -> Option<Action> {
+    if let Event::Paste(text) = evt {
+        return Some(Action::Paste(text));
+    }
+    let Event::Key(key) = evt else {
+        return None;
+    };
+    if let Some(action) = keymap::bound(app, &contexts(app), &key) {
+        return Some(action);
+    }
+    match key.code {
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(Action::InsertChar(c))
+        }
+        _ => None,
+    }
+}
 
-pub async fn handle_event(evt: Event, app: &mut App, _tx: &UnboundedSender<String>) {
-    if let Event::Key(KeyEvent {
-        code, modifiers, ..
-    }) = evt
-    {
-        let reg_mode = app.auth_mode == AuthMode::Register;
-        let input_count = if reg_mode { 3 } else { 2 };
-        let btn_idx = input_count;
+// plain letters only mean something when no text field has focus, so typing an uppercase L
+// in the username is just an L
+fn contexts(app: &App) -> Vec<Context> {
+    let input_count = if app.auth_mode == AuthMode::Register {
+        3
+    } else {
+        2
+    };
+    if app.auth_mode == AuthMode::Register && app.focus == ICON_INPUT_IDX {
+        vec![Context::AuthIcon, Context::AuthButtons, Context::Auth]
+    } else if app.focus >= input_count {
+        vec![Context::AuthButtons, Context::Auth]
+    } else {
+        vec![Context::Auth]
+    }
+}
 
-        let icon_picker_input_idx = 2;
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    let reg_mode = app.auth_mode == AuthMode::Register;
+    let input_count = if reg_mode { 3 } else { 2 };
+    let btn_idx = input_count;
+    let typing = app.focus < input_count;
 
-        match code {
-            KeyCode::Tab => {
-                app.focus = (app.focus + 1) % (input_count + 1);
-            }
-            KeyCode::BackTab | KeyCode::Up => {
-                if app.focus == 0 {
-                    app.focus = input_count;
-                } else {
-                    app.focus -= 1;
-                }
-            }
-            KeyCode::Down => {
-                app.focus = (app.focus + 1) % (input_count + 1);
-            }
-            KeyCode::Char('L') => {
-                app.auth_mode = AuthMode::Login;
-                app.focus = 0;
-                app.input_boxes[0].value.clear();
-                app.input_boxes[1].value.clear();
-                app.input_boxes[0].cursor = 0;
-                app.input_boxes[1].cursor = 0;
-            }
-            KeyCode::Char('R') => {
-                app.auth_mode = AuthMode::Register;
-                app.focus = 0;
-                app.input_boxes[0].value.clear();
-                app.input_boxes[1].value.clear();
-                app.input_boxes[0].cursor = 0;
-                app.input_boxes[1].cursor = 0;
-            }
-            KeyCode::Char('Q') => {
-                app.should_quit = true;
-            }
-            KeyCode::Char('t') if modifiers.contains(KeyModifiers::CONTROL) => {
-                app.auth_mode = if app.auth_mode == AuthMode::Register {
-                    AuthMode::Login
-                } else {
-                    AuthMode::Register
-                };
-                app.focus = 0;
-                app.input_boxes[0].value.clear();
-                app.input_boxes[1].value.clear();
-                app.input_boxes[0].cursor = 0;
-                app.input_boxes[1].cursor = 0;
+    match action {
+        Action::FocusNext => {
+            app.focus = (app.focus + 1) % (input_count + 1);
+        }
+        Action::FocusPrev => {
+            if app.focus == 0 {
+                app.focus = input_count;
+            } else {
+                app.focus -= 1;
             }
-            KeyCode::Enter => {
-                if app.focus == btn_idx && !app.is_loading {
-                    app.is_loading = true;
-                    app.error = None;
-                    let username = app.input_boxes[0].value.trim().to_string();
-                    let password = app.input_boxes[1].value.trim().to_string();
-                    let api_base = "http://back.reetui.hackclub.app";
+        }
+        Action::SetAuthMode(mode) => {
+            app.auth_mode = mode;
+            reset_form(app);
+        }
+        Action::ToggleAuthMode => {
+            app.auth_mode = if reg_mode {
+                AuthMode::Login
+            } else {
+                AuthMode::Register
+            };
+            reset_form(app);
+        }
+        Action::Submit if app.focus == btn_idx && !app.is_loading => {
+            app.error = None;
+            let username = app.input_boxes[0].editor.text().trim().to_string();
+            let password = app.input_boxes[1].editor.text().trim().to_string();
 
-                    if username.is_empty() || password.is_empty() {
-                        app.error = Some("Username and Password required".into());
-                        app.error_time = Some(Instant::now());
-                        app.input_boxes[0].value.clear();
-                        app.input_boxes[1].value.clear();
-                        app.input_boxes[0].cursor = 0;
-                        app.input_boxes[1].cursor = 0;
-                        app.is_loading = false;
-                        return;
-                    }
+            if username.is_empty() || password.is_empty() {
+                app.error = Some("Username and Password required".into());
+                app.error_time = Some(Instant::now());
+                clear_credentials(app);
+                return vec![];
+            }
 
-                    let res = if reg_mode {
-                        register(&username, &password, &app.current_icon, api_base).await
+            app.is_loading = true;
+            return vec![if reg_mode {
+                Effect::Register {
+                    username,
+                    password,
+                    icon: app.current_icon.clone(),
+                }
+            } else {
+                Effect::Login { username, password }
+            }];
+        }
+        Action::InsertChar(c) if typing => app.input_boxes[app.focus].editor.insert_char(c),
+        // these are one line fields, a copied password usually comes with a newline on the end
+        Action::Paste(text) if typing => {
+            let text: String = text.chars().filter(|c| !matches!(c, '\r' | '\n')).collect();
+            app.input_boxes[app.focus].editor.insert_str(&text);
+        }
+        Action::Backspace if typing => app.input_boxes[app.focus].editor.backspace(),
+        Action::DeleteWordBack if typing => app.input_boxes[app.focus].editor.kill_word_back(),
+        Action::KillToStart if typing => app.input_boxes[app.focus].editor.kill_to_start(),
+        Action::CursorLeft if typing => app.input_boxes[app.focus].editor.left(),
+        Action::CursorRight if typing => app.input_boxes[app.focus].editor.right(),
+        Action::Click(ClickTarget::Input(idx, col)) => {
+            app.focus = idx;
+            let input = &mut app.input_boxes[idx];
+            // the column counts the border
+            let cursor = cursor_at_column(input, (col as usize).saturating_sub(1));
+            input.editor.set_cursor(cursor);
+        }
+        Action::Click(ClickTarget::SubmitButton) => {
+            app.focus = btn_idx;
+            return update(app, Action::Submit);
+        }
+        Action::Click(ClickTarget::PrevIcon) => {
+            app.focus = ICON_INPUT_IDX;
+            return update(app, Action::PrevIcon);
+        }
+        Action::Click(ClickTarget::NextIcon) => {
+            app.focus = ICON_INPUT_IDX;
+            return update(app, Action::NextIcon);
+        }
+        Action::Click(ClickTarget::ToggleAuthMode) => return update(app, Action::ToggleAuthMode),
+        Action::PrevIcon => {
+            let len = app.icons().len();
+            app.icon_index = (app.icon_index + len - 1) % len;
+            app.current_icon = app.icons()[app.icon_index].to_string();
+        }
+        Action::NextIcon => {
+            let len = app.icons().len();
+            app.icon_index = (app.icon_index + 1) % len;
+            app.current_icon = app.icons()[app.icon_index].to_string();
+        }
+        Action::Net(AppEvent::AuthFinished(res)) => {
+            app.is_loading = false;
+            match res {
+                Ok(token) => {
+                    app.token = Some(token.clone());
+                    app.page = Page::Home;
+                    return vec![Effect::ConnectChat { token }];
+                }
+                Err(e) => {
+                    let err_msg = if e.contains("409") {
+                        "409: User already exists, use a pretty name :3".to_string()
+                    } else if e.contains("401") {
+                        "Incorrect password, ya forgot ? it's 1234 ofc".to_string()
                     } else {
-                        login(&username, &password, api_base).await
+                        e
                     };
-                    match res {
-                        Ok(token) => {
-                            app.token = Some(token.token.clone());
-                            app.page = crate::app::Page::Home;
-                        }
-                        Err(e) => {
-                            let err_msg = if e.contains("409") {
-                                "409: User already exists, use a pretty name :3".to_string()
-                            } else if e.contains("401") {
-                                "Incorrect password, ya forgot ? it's 1234 ofc".to_string()
-                            } else {
-                                e
-                            };
-                            app.error = Some(err_msg);
-                            app.error_time = Some(Instant::now());
-                            app.input_boxes[0].value.clear();
-                            app.input_boxes[1].value.clear();
-                            app.input_boxes[0].cursor = 0;
-                            app.input_boxes[1].cursor = 0;
-                        }
-                    }
-                    app.is_loading = false;
-                }
-            }
-            KeyCode::Char(c)
-                if app.focus < input_count
-                    && (app.focus == 0
-                        || app.focus == 1
-                        || (reg_mode && app.focus == icon_picker_input_idx)) =>
-            {
-                app.input_boxes[app.focus].value.push(c);
-                app.input_boxes[app.focus].cursor += 1;
-            }
-            KeyCode::Backspace
-                if app.focus < input_count
-                    && (app.focus == 0
-                        || app.focus == 1
-                        || (reg_mode && app.focus == icon_picker_input_idx)) =>
-            {
-                if app.input_boxes[app.focus].cursor > 0 {
-                    app.input_boxes[app.focus].value.pop();
-                    app.input_boxes[app.focus].cursor -= 1;
+                    app.error = Some(err_msg);
+                    app.error_time = Some(Instant::now());
+                    clear_credentials(app);
                 }
             }
-            KeyCode::Left if reg_mode && app.focus == icon_picker_input_idx => {
-                let len = app.icons.len();
-                app.icon_index = (app.icon_index + len - 1) % len;
-                app.current_icon = app.icons[app.icon_index].to_string();
-            }
-            KeyCode::Right if reg_mode && app.focus == icon_picker_input_idx => {
-                let len = app.icons.len();
-                app.icon_index = (app.icon_index + 1) % len;
-                app.current_icon = app.icons[app.icon_index].to_string();
-            }
-            _ => {}
         }
+        _ => {}
     }
+    vec![]
 }
 
-fn rgb_to_color(rgb: &Rgb) -> Color {
-    Color::Rgb(rgb.0, rgb.1, rgb.2)
+fn reset_form(app: &mut App) {
+    app.focus = 0;
+    clear_credentials(app);
+}
+
+// passwords show three characters per byte, see InputBox::display
+fn cursor_at_column(input: &InputBox, col: usize) -> usize {
+    let text = input.editor.text();
+    if !input.is_password {
+        return TextLayout::new(text, usize::MAX).cursor_at(text, 0, col);
+    }
+    let mut cursor = (col / 3).min(text.len());
+    while !text.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    cursor
+}
+
+fn cursor_column(input: &InputBox) -> usize {
+    let text = &input.editor.text()[..input.editor.cursor()];
+    if input.is_password {
+        text.len() * 3
+    } else {
+        str_width(text)
+    }
 }
 
-fn get_theme() -> Theme {
-    let theme_path = PathBuf::from("theme.json");
-    let data = fs::read_to_string(theme_path).expect("theme.json not found");
-    serde_json::from_str(&data).expect("Invalid theme.json")
+pub fn clear_credentials(app: &mut App) {
+    app.input_boxes[0].editor.clear();
+    app.input_boxes[1].editor.clear();
 }
 
 pub fn ui(f: &mut Frame, app: &mut App) {
-    let theme = get_theme();
+    let theme = &app.theme;
+    let glyphs = app.glyphs();
 
     f.render_widget(
         Block::default().style(Style::default().bg(Color::Reset).fg(Color::Reset)),
@@ -198,12 +231,12 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             .title(Span::styled(
                 box_title,
                 Style::default()
-                    .fg(rgb_to_color(&theme.text))
+                    .fg(theme.text)
                     .add_modifier(Modifier::BOLD | Modifier::ITALIC),
             ))
             .borders(Borders::ALL)
-            .border_type(BorderType::Rounded)
-            .border_style(Style::default().fg(rgb_to_color(&theme.border))),
+            .border_set(glyphs.border)
+            .border_style(Style::default().fg(theme.border)),
         main_area,
     );
 
@@ -225,80 +258,78 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .direction(Direction::Vertical)
         .constraints(constraints)
         .split(inner);
-
-    let icon_idx = 2;
+    let mut click_areas = Vec::new();
 
     for (idx, input) in app.input_boxes.iter().take(visible_inputs).enumerate() {
         let focus = app.focus == idx;
         let input_area = rows[idx];
+        let text_area = input_area.inner(Margin {
+            vertical: 1,
+            horizontal: 1,
+        });
+        // the column gets filled in when the click comes
+        click_areas.push((input_area, ClickTarget::Input(idx, 0)));
         let border_style = if focus {
-            Style::default()
-                .fg(rgb_to_color(&theme.border_focus))
-                .add_modifier(Modifier::BOLD)
+            theme.focused(theme.border_focus)
         } else {
-            Style::default().fg(rgb_to_color(&theme.border))
+            Style::default().fg(theme.border)
         };
 
-        if reg_mode && idx == icon_idx {
-            let icons = &app.icons;
-            let center = app.icon_index;
-            let len = icons.len();
-
-            let indices: Vec<usize> = (-2..=2)
-                .map(|offset| ((center as isize + offset + len as isize) % len as isize) as usize)
-                .collect();
-
-            let mut spans = Vec::with_capacity(5 * 2 - 1);
-
-            for (pos, &i) in indices.iter().enumerate() {
-                if pos == 2 {
-                    spans.push(Span::styled(
-                        icons[i],
-                        Style::default()
-                            .fg(rgb_to_color(&theme.button_focus))
-                            .add_modifier(Modifier::BOLD),
-                    ));
-                } else {
-                    spans.push(Span::styled(
-                        icons[i],
-                        Style::default()
-                            .fg(Color::DarkGray)
-                            .add_modifier(Modifier::DIM),
-                    ));
-                }
-                if pos != 4 {
-                    spans.push(Span::raw(" "));
-                }
-            }
-
-            let icon_para = Paragraph::new(Line::from(spans))
+        if reg_mode && idx == ICON_INPUT_IDX {
+            let icon_para = Paragraph::new(carousel(app.icons(), app.icon_index, 2, theme))
                 .alignment(Alignment::Center)
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded)
+                        .border_set(glyphs.border)
                         .border_style(border_style)
                         .title(Span::styled(
                             input.label.clone(),
                             Style::default()
-                                .fg(Color::DarkGray)
+                                .fg(theme.muted)
                                 .add_modifier(Modifier::ITALIC),
                         )),
                 );
             f.render_widget(icon_para, input_area);
+            // arrows at either end for the mouse
+            let arrow = Style::default().fg(theme.muted);
+            let prev = Rect {
+                width: 1,
+                ..text_area
+            };
+            let next = Rect {
+                x: text_area.right().saturating_sub(1),
+                width: 1,
+                ..text_area
+            };
+            f.render_widget(Paragraph::new(Span::styled("<", arrow)), prev);
+            f.render_widget(Paragraph::new(Span::styled(">", arrow)), next);
+            click_areas.push((prev, ClickTarget::PrevIcon));
+            click_areas.push((next, ClickTarget::NextIcon));
         } else {
+            if focus && app.cursor_tick_state {
+                let col = cursor_column(input) as u16;
+                if col < text_area.width {
+                    f.set_cursor_position((text_area.x + col, text_area.y));
+                }
+            }
+
             f.render_widget(
                 Paragraph::new(input.display())
-                    .style(Style::default().fg(rgb_to_color(&theme.text)))
+                    .style(if focus {
+                        Style::default().fg(theme.text).bg(theme.input_hover)
+                    } else {
+                        Style::default().fg(theme.text)
+                    })
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
-                            .border_type(BorderType::Rounded)
+                            .border_set(glyphs.border)
                             .border_style(border_style)
                             .title(Span::styled(
                                 input.label.clone(),
                                 Style::default()
-                                    .fg(Color::DarkGray)
+                                    .fg(theme.muted)
                                     .add_modifier(Modifier::ITALIC),
                             )),
                     )
@@ -323,12 +354,10 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         }
     };
     let btn_style = if btn_focus {
-        Style::default()
-            .fg(rgb_to_color(&theme.button_focus))
-            .add_modifier(Modifier::BOLD)
+        theme.focused(theme.button_focus)
     } else {
         Style::default()
-            .fg(rgb_to_color(&theme.button))
+            .fg(theme.button)
             .add_modifier(Modifier::BOLD)
     };
     let btn_area = rows[btn_idx + 1];
@@ -337,33 +366,55 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_type(BorderType::Rounded)
+                .border_set(glyphs.border)
                 .border_style(if btn_focus {
                     Style::default()
-                        .fg(rgb_to_color(&theme.button_focus))
+                        .fg(theme.button_focus)
                         .add_modifier(Modifier::BOLD)
                 } else {
-                    Style::default().fg(rgb_to_color(&theme.button))
+                    Style::default().fg(theme.button)
                 })
                 .title(""),
         );
     f.render_widget(btn_para, btn_area);
+    click_areas.push((btn_area, ClickTarget::SubmitButton));
 
+    // whatever the keymap says, so the hint stays right after a rebind
+    let key = |action| {
+        app.keymap
+            .keys_for(&contexts(app), action)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| "?".to_string())
+    };
     let help_text = if reg_mode {
-        "Already have an account? Press [L] to switch to Login."
+        format!(
+            "Already have an account? Press [{}] to switch to Login.",
+            key("toggle_auth_mode")
+        )
     } else {
-        "Don't have an account? Press [R] to switch to Register."
+        format!(
+            "Don't have an account? Press [{}] to switch to Register.",
+            key("toggle_auth_mode")
+        )
     };
     let help_line = Line::from(vec![
         Span::styled(
-            help_text,
+            help_text.clone(),
             Style::default()
-                .fg(Color::DarkGray)
+                .fg(theme.muted)
                 .add_modifier(Modifier::ITALIC),
         ),
         Span::styled(
-            "    Q: Quit    Tab/Shift+Tab: Move | Enter: Submit",
-            Style::default().fg(Color::Gray),
+            format!(
+                "    {}: Quit    {}/{}: Move | {}: Submit | {}: Theme",
+                key("quit"),
+                key("focus_next"),
+                key("focus_prev"),
+                key("submit"),
+                key("theme_picker")
+            ),
+            Style::default().fg(theme.hint),
         ),
     ]);
     let area = f.area();
@@ -373,12 +424,20 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         width: area.width.saturating_sub(2),
         height: 1,
     };
+    click_areas.push((
+        Rect {
+            width: (str_width(&help_text) as u16).min(bottom_area.width),
+            ..bottom_area
+        },
+        ClickTarget::ToggleAuthMode,
+    ));
     f.render_widget(
         Paragraph::new(help_line)
             .alignment(Alignment::Left)
             .block(Block::default().borders(Borders::NONE)),
         bottom_area,
     );
+    app.click_areas = click_areas;
 
     if let Some(ref err) = app.error {
         let error_area = Rect {
@@ -388,21 +447,47 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             height: 1,
         };
         f.render_widget(
-            Paragraph::new(Span::styled(
-                err,
-                Style::default()
-                    .fg(Color::Red)
-                    .bg(Color::Reset)
-                    .add_modifier(Modifier::BOLD),
-            ))
-            .alignment(Alignment::Left)
-            .block(Block::default().borders(Borders::NONE)),
+            Paragraph::new(Span::styled(err, theme.error()))
+                .alignment(Alignment::Left)
+                .block(Block::default().borders(Borders::NONE)),
             error_area,
         );
     }
 }
 
-fn fixed_rect_in_center(area: Rect, width: u16, height: u16) -> Rect {
+// the picked item in the middle with `side` neighbours each way, wrapping around. lists too
+// short to wrap just show everything
+pub fn carousel(items: &[&str], center: usize, side: usize, theme: &Theme) -> Line<'static> {
+    let len = items.len();
+    let indices: Vec<usize> = if len <= side * 2 + 1 {
+        (0..len).collect()
+    } else {
+        (-(side as isize)..=side as isize)
+            .map(|offset| ((center as isize + offset + len as isize) % len as isize) as usize)
+            .collect()
+    };
+
+    let mut spans = Vec::with_capacity(indices.len() * 2);
+    for (pos, &i) in indices.iter().enumerate() {
+        if pos > 0 {
+            spans.push(Span::raw(" "));
+        }
+        if i == center {
+            spans.push(Span::styled(
+                items[i].to_string(),
+                theme.focused(theme.button_focus),
+            ));
+        } else {
+            spans.push(Span::styled(
+                items[i].to_string(),
+                Style::default().fg(theme.muted).add_modifier(Modifier::DIM),
+            ));
+        }
+    }
+    Line::from(spans)
+}
+
+pub fn fixed_rect_in_center(area: Rect, width: u16, height: u16) -> Rect {
     let x = area.x + (area.width.saturating_sub(width)) / 2;
     let y = area.y + (area.height.saturating_sub(height)) / 2;
     Rect::new(x, y, width.min(area.width), height.min(area.height))
@@ -0,0 +1,254 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// how long to wait for the terminal to say what color its background is. the DA1 answer
+// normally ends the wait long before this, it's only sat out by a terminal that answers
+// neither, so it can be generous enough for a slow ssh link
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+// what came back from the OSC 11 query, kept around for /diag
+#[derive(Clone, Debug, Default)]
+pub struct Detection {
+    pub rgb: Option<(u8, u8, u8)>,
+    // the terminal answered the DA1 we send after the query, so it heard us but may just not
+    // know OSC 11
+    pub answered: bool,
+    pub took: Duration,
+}
+
+impl Detection {
+    pub fn background(&self) -> Option<Background> {
+        let (r, g, b) = self.rgb?;
+        let luminance = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+        Some(if luminance > 127.5 {
+            Background::Light
+        } else {
+            Background::Dark
+        })
+    }
+}
+
+// asks for the background color (OSC 11) and right after that for the device attributes
+// (DA1). every terminal answers DA1, so once that shows up there's no OSC 11 answer coming
+// and we don't have to sit out the whole timeout. has to run in raw mode and before anything
+// else starts reading stdin. whatever got typed meanwhile comes back as keys, so it isn't lost
+pub fn detect(out: &mut impl Write) -> (Detection, Vec<KeyEvent>) {
+    let start = Instant::now();
+    if write!(out, "\x1b]11;?\x1b\\\x1b[c")
+        .and_then(|_| out.flush())
+        .is_err()
+    {
+        return (Detection::default(), Vec::new());
+    }
+    let reply = read_reply(start + QUERY_TIMEOUT);
+    let detection = Detection {
+        rgb: parse_osc11(&reply),
+        answered: da1_end(&reply).is_some(),
+        took: start.elapsed(),
+    };
+    (detection, typeahead(&strip_replies(&reply)))
+}
+
+// where the DA1 reply (ESC [ ? ... c) ends, if it came in yet
+fn da1_end(buf: &[u8]) -> Option<usize> {
+    let start = buf.windows(3).position(|w| w == b"\x1b[?")?;
+    buf[start..]
+        .iter()
+        .position(|&b| b == b'c')
+        .map(|i| start + i + 1)
+}
+
+#[cfg(unix)]
+fn read_reply(deadline: Instant) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 256];
+    while da1_end(&buf).is_none() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        let mut fds = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // poll first so a terminal that never answers can't leave us stuck in read
+        let ready = unsafe { libc::poll(&mut fds, 1, left.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        let n = unsafe { libc::read(libc::STDIN_FILENO, chunk.as_mut_ptr().cast(), chunk.len()) };
+        if n <= 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n as usize]);
+    }
+    // gave up waiting, so the rest of an answer may still be on its way. throw away what's
+    // queued, or it turns up later as keys in the login form
+    if da1_end(&buf).is_none() {
+        unsafe { libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH) };
+    }
+    buf
+}
+
+#[cfg(not(unix))]
+fn read_reply(_deadline: Instant) -> Vec<u8> {
+    Vec::new()
+}
+
+// the input without the OSC 11 and DA1 answers, whatever the user typed while we waited
+fn strip_replies(buf: &[u8]) -> Vec<u8> {
+    let mut rest = buf.to_vec();
+    if let Some(start) = rest.windows(5).position(|w| w == b"\x1b]11;") {
+        // ends on BEL or ST (ESC \\), an answer cut short takes the rest with it
+        let end = rest[start..]
+            .windows(2)
+            .position(|w| w[0] == 0x07 || w == b"\x1b\\")
+            .map_or(rest.len(), |i| {
+                start + i + if rest[start + i] == 0x07 { 1 } else { 2 }
+            });
+        rest.drain(start..end);
+    }
+    if let Some(end) = da1_end(&rest) {
+        let start = rest.windows(3).position(|w| w == b"\x1b[?").unwrap_or(0);
+        rest.drain(start..end);
+    }
+    rest
+}
+
+// plain typing back into keys. escape sequences (arrows and such) get dropped, they'd need
+// crossterm's whole parser and nobody reaches for them in the first second
+fn typeahead(bytes: &[u8]) -> Vec<KeyEvent> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut keys = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' => {
+                if chars.next_if_eq(&'[').is_some() {
+                    while chars.next_if(|c| !('@'..='~').contains(c)).is_some() {}
+                    chars.next();
+                    continue;
+                }
+                KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)
+            }
+            '\r' | '\n' => KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            '\t' => KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE),
+            '\x7f' | '\x08' => KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+            '\x01'..='\x1a' => KeyEvent::new(
+                KeyCode::Char((c as u8 - 1 + b'a') as char),
+                KeyModifiers::CONTROL,
+            ),
+            c if c.is_control() => continue,
+            c => KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+// ESC ] 11 ; rgb:RRRR/GGGG/BBBB (BEL or ST), each channel is 1 to 4 hex digits
+fn parse_osc11(buf: &[u8]) -> Option<(u8, u8, u8)> {
+    let text = String::from_utf8_lossy(buf);
+    let rest = &text[text.find("]11;")? + 4..];
+    let rest = rest
+        .strip_prefix("rgba:")
+        .or_else(|| rest.strip_prefix("rgb:"))?;
+    let end = rest.find(['\x07', '\x1b']).unwrap_or(rest.len());
+    let mut channels = rest[..end].split('/').map(|c| {
+        if c.is_empty() || c.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(c, 16).ok()?;
+        let max = (1u32 << (4 * c.len())) - 1;
+        Some((value * 255 / max) as u8)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc11_answers() {
+        assert_eq!(
+            parse_osc11(b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\"),
+            Some((255, 255, 255))
+        );
+        assert_eq!(
+            parse_osc11(b"\x1b]11;rgb:0000/0000/0000\x07"),
+            Some((0, 0, 0))
+        );
+        // one to four digits a channel, all scaled to 0-255
+        assert_eq!(
+            parse_osc11(b"\x1b]11;rgb:f/80/123\x07"),
+            Some((255, 128, 18))
+        );
+        assert_eq!(
+            parse_osc11(b"\x1b]11;rgba:1e1e/1e1e/2e2e/ffff\x1b\\"),
+            Some((30, 30, 46))
+        );
+        // with the DA1 answer right behind it
+        assert_eq!(
+            parse_osc11(b"\x1b]11;rgb:2828/2c2c/3434\x1b\\\x1b[?62;22c"),
+            Some((40, 44, 52))
+        );
+    }
+
+    #[test]
+    fn not_osc11_answers() {
+        assert_eq!(parse_osc11(b""), None);
+        assert_eq!(parse_osc11(b"\x1b[?62;22c"), None);
+        assert_eq!(parse_osc11(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+        assert_eq!(parse_osc11(b"\x1b]11;rgb:fffff/0/0\x07"), None);
+        assert_eq!(parse_osc11(b"\x1b]11;rgb:zz/00/00\x07"), None);
+        assert_eq!(parse_osc11(b"\x1b]11;#ffffff\x07"), None);
+    }
+
+    #[test]
+    fn da1() {
+        assert_eq!(da1_end(b"\x1b[?62;22c"), Some(9));
+        assert_eq!(da1_end(b"ab\x1b[?1;2cxy"), Some(9));
+        assert_eq!(da1_end(b"\x1b[?62;2"), None);
+    }
+
+    #[test]
+    fn typing_during_the_probe_survives() {
+        let reply = b"ab\x1b]11;rgb:0/0/0\x1b\\c\x1b[?62c\r";
+        let keys = typeahead(&strip_replies(reply));
+        let codes: Vec<KeyCode> = keys.iter().map(|k| k.code).collect();
+        assert_eq!(
+            codes,
+            [
+                KeyCode::Char('a'),
+                KeyCode::Char('b'),
+                KeyCode::Char('c'),
+                KeyCode::Enter
+            ]
+        );
+        assert!(typeahead(&strip_replies(b"\x1b]11;rgb:0/0/0\x07\x1b[?62c")).is_empty());
+    }
+
+    #[test]
+    fn typeahead_keys() {
+        let keys = typeahead("é\x7f\x15\t\x1b[A\x1b".as_bytes());
+        assert_eq!(
+            keys,
+            [
+                KeyEvent::new(KeyCode::Char('é'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL),
+                KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            ]
+        );
+    }
+}
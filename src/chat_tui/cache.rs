@@ -0,0 +1,337 @@
+use ratatui::{
+    style::{Modifier, Style},
+    text::Line,
+};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use super::data::ChatMessage;
+use super::links;
+use super::utils::{wrap_with_prefixes, LinkRegion, Segment};
+use crate::glyphs::Glyphs;
+use crate::text_layout::str_width;
+use crate::theme::Theme;
+
+// a wrapped message body. headers aren't cached, they depend on the clock
+pub struct CachedMessage {
+    pub lines: Vec<Line<'static>>,
+    pub links: Vec<LinkRegion>,
+}
+
+struct Entry {
+    id: u64,
+    // the name row above the body, when the one before is someone else
+    header: bool,
+    // body rows, a guess until the message has been wrapped at the current width
+    rows: usize,
+    layout: Option<CachedMessage>,
+}
+
+impl Entry {
+    fn height(&self) -> usize {
+        self.header as usize + self.rows
+    }
+}
+
+// one entry per message in app.chat_messages, in the same order, plus the row each of them
+// starts on. a frame only wraps the messages it actually shows, everything else keeps a cheap
+// guess at its height until it scrolls into view
+#[derive(Default)]
+pub struct MessageLayoutCache {
+    // (width, numbered links), both change how many rows a message takes
+    shape: (usize, bool),
+    generation: u64,
+    entries: VecDeque<Entry>,
+    // rows above each entry, only good below dirty_from
+    starts: Vec<usize>,
+    dirty_from: usize,
+}
+
+impl MessageLayoutCache {
+    // catches up with the history and throws away whatever the width, the theme or the link
+    // markers made stale
+    pub fn sync(
+        &mut self,
+        messages: &VecDeque<ChatMessage>,
+        width: usize,
+        glyphs: &Glyphs,
+        generation: u64,
+        numbered_links: bool,
+    ) {
+        let Some(first) = messages.front() else {
+            self.clear();
+            return;
+        };
+        // old messages only ever fall off the front
+        while self.entries.front().is_some_and(|e| e.id < first.id) {
+            self.entries.pop_front();
+            self.dirty_from = 0;
+        }
+        if self.entries.front().is_some_and(|e| e.id != first.id) {
+            self.clear();
+        }
+        if let Some(e) = self.entries.front_mut() {
+            if !e.header {
+                e.header = true;
+                self.dirty_from = 0;
+            }
+        }
+
+        let body_width = width.saturating_sub(str_width(glyphs.body_prefix));
+        if self.shape != (width, numbered_links) {
+            self.shape = (width, numbered_links);
+            for (e, msg) in self.entries.iter_mut().zip(messages) {
+                e.rows = estimate_rows(&msg.content, body_width);
+                e.layout = None;
+            }
+            self.dirty_from = 0;
+        }
+        if self.generation != generation {
+            // colours don't move anything, the heights stay
+            self.generation = generation;
+            for e in &mut self.entries {
+                e.layout = None;
+            }
+        }
+
+        let known = self.entries.len();
+        self.dirty_from = self.dirty_from.min(known);
+        for (i, msg) in messages.iter().enumerate().skip(known) {
+            let header = i == 0 || messages[i - 1].user != msg.user;
+            self.entries.push_back(Entry {
+                id: msg.id,
+                header,
+                rows: estimate_rows(&msg.content, body_width),
+                layout: None,
+            });
+        }
+    }
+
+    fn rebuild_starts(&mut self) {
+        let len = self.entries.len();
+        self.starts.resize(len, 0);
+        for i in self.dirty_from.max(1).min(len)..len {
+            self.starts[i] = self.starts[i - 1] + self.entries[i - 1].height();
+        }
+        self.dirty_from = len;
+    }
+
+    pub fn total_rows(&mut self) -> usize {
+        match self.entries.len() {
+            0 => 0,
+            len => self.start(len - 1) + self.entries[len - 1].height(),
+        }
+    }
+
+    // the row message i starts on, its header included
+    pub fn start(&mut self, i: usize) -> usize {
+        if self.dirty_from <= i {
+            self.rebuild_starts();
+        }
+        self.starts[i]
+    }
+
+    pub fn header(&self, i: usize) -> bool {
+        self.entries[i].header
+    }
+
+    // the first message that reaches down to row or past it
+    pub fn message_at(&mut self, row: usize) -> usize {
+        if self.dirty_from < self.entries.len() {
+            self.rebuild_starts();
+        }
+        let (mut lo, mut hi) = (0, self.entries.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.starts[mid] + self.entries[mid].height() <= row {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    // wraps message i if it hasn't been at this width and theme yet, and puts its real height
+    // in place of the guess
+    pub fn layout(
+        &mut self,
+        i: usize,
+        msg: &ChatMessage,
+        theme: &Theme,
+        glyphs: &Glyphs,
+    ) -> &CachedMessage {
+        let (width, numbered_links) = self.shape;
+        let entry = &mut self.entries[i];
+        if entry.layout.is_none() {
+            let (lines, links) = layout_message(msg, width, theme, glyphs, numbered_links);
+            if lines.len() != entry.rows {
+                entry.rows = lines.len();
+                self.dirty_from = self.dirty_from.min(i + 1);
+            }
+            entry.layout = Some(CachedMessage { lines, links });
+        }
+        entry.layout.as_ref().expect("filled in above")
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.starts.clear();
+        self.dirty_from = 0;
+    }
+}
+
+// rows a message will probably take, one per started body_width bytes of every line. good
+// enough for the scrollbar until the message is actually on screen
+fn estimate_rows(content: &str, body_width: usize) -> usize {
+    content
+        .split('\n')
+        .map(|line| line.len().div_ceil(body_width.max(1)).max(1))
+        .sum()
+}
+
+fn layout_message(
+    msg: &ChatMessage,
+    width: usize,
+    theme: &Theme,
+    glyphs: &Glyphs,
+    numbered_links: bool,
+) -> (Vec<Line<'static>>, Vec<LinkRegion>) {
+    let text_style = Style::default().fg(theme.text);
+    let link_style = Style::default()
+        .fg(theme.link)
+        .add_modifier(Modifier::UNDERLINED);
+    let marker_style = Style::default().fg(theme.muted);
+
+    let segments = message_segments(
+        &msg.content,
+        text_style,
+        link_style,
+        marker_style,
+        numbered_links.then_some(msg.link_base),
+    );
+    wrap_with_prefixes(
+        &segments,
+        width,
+        glyphs.body_prefix,
+        Style::default().fg(theme.muted),
+    )
+}
+
+// splits a message into plain text and link runs, when the terminal can't do OSC 8 every link
+// gets a [n] marker after it for /open n
+fn message_segments<'a>(
+    content: &'a str,
+    text_style: Style,
+    link_style: Style,
+    marker_style: Style,
+    mut link_number: Option<usize>,
+) -> Vec<Segment<'a>> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    for range in links::find_urls(content) {
+        if range.start > pos {
+            segments.push(Segment {
+                text: Cow::Borrowed(&content[pos..range.start]),
+                style: text_style,
+                url: None,
+            });
+        }
+        segments.push(Segment {
+            text: Cow::Borrowed(&content[range.clone()]),
+            style: link_style,
+            url: Some(&content[range.clone()]),
+        });
+        if let Some(n) = link_number.as_mut() {
+            *n += 1;
+            segments.push(Segment {
+                text: Cow::Owned(format!(" [{}]", n)),
+                style: marker_style,
+                url: None,
+            });
+        }
+        pos = range.end;
+    }
+    if pos < content.len() || segments.is_empty() {
+        segments.push(Segment {
+            text: Cow::Borrowed(&content[pos..]),
+            style: text_style,
+            url: None,
+        });
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyphs::GlyphSet;
+
+    fn history(contents: &[(&str, &str)]) -> VecDeque<ChatMessage> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(id, (user, content))| ChatMessage {
+                user: user.to_string(),
+                content: content.to_string(),
+                id: id as u64,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn headers_only_when_the_sender_changes() {
+        let messages = history(&[("a", "1"), ("a", "2"), ("b", "3"), ("a", "4")]);
+        let mut cache = MessageLayoutCache::default();
+        cache.sync(&messages, 40, GlyphSet::Ascii.glyphs(), 0, false);
+        let headers: Vec<bool> = (0..4).map(|i| cache.header(i)).collect();
+        assert_eq!(headers, [true, false, true, true]);
+        // every message is one body row
+        assert_eq!(cache.total_rows(), 7);
+        assert_eq!(cache.start(2), 3);
+    }
+
+    #[test]
+    fn dropping_the_front_gives_the_new_first_a_header() {
+        let mut messages = history(&[("a", "1"), ("a", "2"), ("a", "3")]);
+        let mut cache = MessageLayoutCache::default();
+        cache.sync(&messages, 40, GlyphSet::Ascii.glyphs(), 0, false);
+        assert_eq!(cache.total_rows(), 4);
+        messages.pop_front();
+        cache.sync(&messages, 40, GlyphSet::Ascii.glyphs(), 0, false);
+        assert!(cache.header(0));
+        assert_eq!(cache.total_rows(), 3);
+        assert_eq!(cache.start(1), 2);
+    }
+
+    #[test]
+    fn wrapping_replaces_the_guess() {
+        let theme = crate::theme::preset("dark").expect("dark theme");
+        let glyphs = GlyphSet::Ascii.glyphs();
+        // word wrapping needs more rows than bytes / width
+        let messages = history(&[("a", "aaa bbb ccc ddd"), ("a", "x")]);
+        let mut cache = MessageLayoutCache::default();
+        let width = 6 + str_width(glyphs.body_prefix);
+        cache.sync(&messages, width, glyphs, 0, false);
+        assert_eq!(cache.start(1), 1 + 3);
+        let rows = cache.layout(0, &messages[0], &theme, glyphs).lines.len();
+        assert_eq!(rows, 4);
+        assert_eq!(cache.start(1), 1 + 4);
+        assert_eq!(cache.message_at(5), 1);
+        assert_eq!(cache.message_at(4), 0);
+    }
+
+    #[test]
+    fn numbered_links_rewrap() {
+        let theme = crate::theme::preset("dark").expect("dark theme");
+        let glyphs = GlyphSet::Ascii.glyphs();
+        let messages = history(&[("a", "see https://example.com")]);
+        let mut cache = MessageLayoutCache::default();
+        cache.sync(&messages, 80, glyphs, 0, false);
+        let plain = cache.layout(0, &messages[0], &theme, glyphs).lines[0].width();
+        cache.sync(&messages, 80, glyphs, 0, true);
+        let numbered = cache.layout(0, &messages[0], &theme, glyphs).lines[0].width();
+        assert!(numbered > plain);
+    }
+}
@@ -1,20 +1,18 @@
 use serde::Deserialize;
 
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ChatMessage {
     pub user: String,
     pub icon: Option<String>,
     pub content: String,
     pub timestamp: Option<i64>,
-}
-
-#[derive(Deserialize, Clone, Debug)]
-pub struct Rgb(pub u8, pub u8, pub u8);
-
-#[derive(Deserialize, Debug)]
-pub struct Theme {
-    pub border: Rgb,
-    pub border_focus: Rgb,
-    pub button_focus: Rgb,
-    pub text: Rgb,
+    // local only, set when the message lands in the history
+    #[serde(skip)]
+    pub id: u64,
+    // number of the last link before this message, links get numbered as they arrive
+    #[serde(skip)]
+    pub link_base: usize,
+    // made up by the client, gets the system icon of whatever glyph set is in use
+    #[serde(skip)]
+    pub system: bool,
 }
@@ -1,229 +1,340 @@
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc::UnboundedSender;
-use unicode_width::UnicodeWidthChar;
 
-use super::utils::{cursor_line_col, split_input_lines};
+use crate::action::{Action, AppEvent, ClickTarget, Effect};
 use crate::app::App;
+use crate::keymap::{self, Context};
+use crate::profile::Profile;
+use crate::text_layout::TextLayout;
+use crate::vim::Mode;
+use crate::{commands, completion, emoji, paste_prompt};
 
-// Key Actions:
+// Key Actions (the defaults, keymap.rs has them all and keybindings.toml in the config dir
+// can change any of them, e.g. [chat] "ctrl+s" = "send"):
 // Character input -> Inserts character at cursor position
+// Paste -> Goes in as one edit with its newlines, big ones ask whether to fence it in a code block
 // Backspace -> Deletes the character before the cursor
 // Delete -> Deletes the character at the cursor position
 // Left Arrow -> Moves the cursor one character to the left
 // Right Arrow -> Moves the cursor one character to the right
-// Home -> Moves the cursor to the beginning of the current line
-// End -> Moves the cursor to the end of the current line
+// Home / Ctrl + A -> Moves the cursor to the beginning of the current line
+// End / Ctrl + E -> Moves the cursor to the end of the current line
+// Ctrl/Alt + Left/Right, Alt + B/F -> Moves a word at a time
+// Ctrl + W, Alt/Ctrl + Backspace -> Cuts the word before the cursor
+// Alt + D, Ctrl + Delete -> Cuts the word after the cursor
+// Ctrl + U / Ctrl + K -> Cuts to the start / end of the line
+// Ctrl + Y -> Pastes the last cut, Alt + Y right after swaps it for the one before
+// Ctrl + T -> Swaps the two characters around the cursor
+// Ctrl + Z (or Ctrl + _) -> Undo, Ctrl + Shift + Z -> Redo
+// Up on the first line / Down on the last line -> Walks through sent messages
+// Ctrl + X Ctrl + E or /edit -> Writes the message in $VISUAL / $EDITOR
+// Tab / Shift + Tab -> Completes /commands, @names and :emoji:, again to cycle
+// Alt + E or /emoji -> Emoji picker, :shortcode: turns into the emoji when sent
+// Ctrl + R -> Searches sent messages, again for older matches, Enter keeps, Esc cancels
 // Enter -> Sends the message (unless Shift is held)
-// Shift + Enter -> Inserts a newline
+// Shift + Enter -> Inserts a newline, only on terminals with the kitty keyboard protocol,
+// everywhere else it's a plain Enter. Alt + Enter and Ctrl + J work on all of them, the
+// composer's top border says which one to use
 // (Implicit) Fast Enter -> Prevents spamming messages (if pressed too quickly)
-// Escape -> Currently does nothing
-// Ctrl + Up Arrow -> Scrolls chat content up
-// Ctrl + Down Arrow -> Scrolls chat content down
+// Escape -> Cancels a search, with vim on leaves insert mode
+// With "vim": true in config.json the composer is modal, Esc for normal mode, i/a/o to type,
+// hjkl w b e 0 $ to move, d/c/y + motion (dd, cc, yy, counts work), x, p/P, u / Ctrl + R
+// to undo/redo and v for visual mode. the box title says which mode it's in
+// F1, ? in an empty composer or /help -> Every key and command, type to search, Esc closes
+// F2 or /theme -> Theme picker, /theme <name> switches straight away
+// F12 or /diag -> What the client found out about the terminal
+// /something -> Runs a command (see commands.rs or /help), //something sends /something
+// Ctrl + Up Arrow / Mouse wheel -> Scrolls chat content up
+// Ctrl + Down Arrow / Mouse wheel -> Scrolls chat content down
+// Click in the composer -> Puts the cursor there, click a name -> What we know about them
+// F10 or /mouse -> Lets go of the mouse so the terminal can select text, again to take it back
 // (Implicit) Scrolling to bottom -> Re-enables auto-scroll
 // Up Arrow (in input) -> Moves the input cursor up one line
 // Down Arrow (in input) -> Moves the input cursor down one line
 // Other keys -> Catches any other unhandled key presses
 
-pub async fn handle_event(
-    evt: Event,
-    app: &mut App,
-    tx: &UnboundedSender<String>,
-    input_width: usize,
-) {
-    if let Event::Key(KeyEvent {
-        code, modifiers, ..
-    }) = evt
-    {
-        match code {
-            KeyCode::Char(c) => {
-                app.chat_input.insert(app.input_cursor, c);
-                app.input_cursor += c.len_utf8();
-            }
-            KeyCode::Backspace => {
-                if app.input_cursor > 0 {
-                    let mut char_to_remove_start_byte_idx = 0;
-                    let mut prev_char_len_bytes = 0;
-                    for (idx, ch) in app.chat_input.char_indices() {
-                        if idx + ch.len_utf8() == app.input_cursor {
-                            char_to_remove_start_byte_idx = idx;
-                            prev_char_len_bytes = ch.len_utf8();
-                            break;
-                        }
-                    }
-                    if prev_char_len_bytes > 0 {
-                        app.chat_input.remove(char_to_remove_start_byte_idx);
-                        app.input_cursor -= prev_char_len_bytes;
-                    }
-                }
-            }
-            KeyCode::Delete => {
-                if app.input_cursor < app.chat_input.len() {
-                    let mut char_to_remove_start_byte_idx = 0;
-                    let mut char_len_bytes = 0;
-                    for (idx, ch) in app.chat_input.char_indices() {
-                        if idx == app.input_cursor {
-                            char_to_remove_start_byte_idx = idx;
-                            char_len_bytes = ch.len_utf8();
-                            break;
-                        }
-                    }
-                    if char_len_bytes > 0 {
-                        app.chat_input.remove(char_to_remove_start_byte_idx);
-                    }
-                }
-            }
-            KeyCode::Left => {
-                if app.input_cursor > 0 {
-                    app.input_cursor = app.chat_input[..app.input_cursor]
-                        .char_indices()
-                        .last()
-                        .map_or(0, |(idx, _)| idx);
-                }
-            }
-            KeyCode::Right => {
-                if app.input_cursor < app.chat_input.len() {
-                    app.input_cursor = app.chat_input[app.input_cursor..]
-                        .char_indices()
-                        .next()
-                        .map_or(app.chat_input.len(), |(idx, ch)| {
-                            app.input_cursor + idx + ch.len_utf8()
-                        });
-                }
+pub fn map_event(evt: Event, app: &App) -> Option<Action> {
+    if let Event::Paste(text) = evt {
+        return Some(Action::Paste(text));
+    }
+    let Event::Key(key) = evt else {
+        return None;
+    };
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    if let Some(action) = vim_key(app, key.code, ctrl || alt) {
+        return Some(action);
+    }
+    if let Some(action) = keymap::bound(app, &[Context::Chat], &key) {
+        return Some(action);
+    }
+    match key.code {
+        KeyCode::Char(c) if !ctrl && !alt => Some(Action::InsertChar(c)),
+        _ => None,
+    }
+}
+
+// with vim on, Esc leaves insert mode and normal/visual take the plain keys. Enter still sends,
+// Ctrl+R is redo like in vim, and the other ctrl keys and arrows up/down work as usual
+fn vim_key(app: &App, code: KeyCode, ctrl_or_alt: bool) -> Option<Action> {
+    let vim = app.vim.as_ref()?;
+    if app.history.search.is_some() {
+        return None;
+    }
+    match (vim.mode, code) {
+        (_, KeyCode::Esc) if !ctrl_or_alt => Some(Action::VimKey(code)),
+        (Mode::Insert, _) => None,
+        (_, KeyCode::Char('r')) if ctrl_or_alt => Some(Action::Redo),
+        (_, KeyCode::Char(_) | KeyCode::Backspace) if !ctrl_or_alt => Some(Action::VimKey(code)),
+        (_, KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End) if !ctrl_or_alt => {
+            Some(Action::VimKey(code))
+        }
+        _ => None,
+    }
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    let Some(action) = complete(app, action) else {
+        return vec![];
+    };
+    let Some(action) = search(app, action) else {
+        return vec![];
+    };
+    let input_width = app.input_width;
+    match action {
+        Action::EditExternally => {
+            return vec![Effect::EditExternally(app.composer.text().to_string())];
+        }
+        Action::Net(AppEvent::EditorFinished(Ok(text))) => app.composer.set_text(&text),
+        Action::Net(AppEvent::EditorFinished(Err(e))) => {
+            app.error = Some(e);
+            app.error_time = Some(Instant::now());
+        }
+        Action::VimKey(key) => {
+            if let Some(vim) = &mut app.vim {
+                vim.key(&mut app.composer, key);
             }
-            KeyCode::Home => {
-                let lines = split_input_lines(&app.chat_input, input_width);
-                let (cur_line_idx, _) = cursor_line_col(app.input_cursor, &lines);
-                let mut new_cursor = 0;
-                for i in 0..cur_line_idx {
-                    new_cursor += lines[i].len();
-                }
-                app.input_cursor = new_cursor;
+        }
+        Action::InsertChar(c) => app.composer.insert_char(c),
+        Action::InsertNewline => app.composer.insert_char('\n'),
+        Action::Paste(text) => {
+            let text = paste_prompt::normalize(&text);
+            if paste_prompt::is_large(&text) {
+                app.pending_paste = Some(text);
+            } else {
+                app.composer.insert_str(&text);
             }
-            KeyCode::End => {
-                let lines = split_input_lines(&app.chat_input, input_width);
-                let (cur_line_idx, _) = cursor_line_col(app.input_cursor, &lines);
-                let mut new_cursor = 0;
-                for i in 0..=cur_line_idx {
-                    new_cursor += lines[i].len();
-                }
-                if cur_line_idx < lines.len() - 1 {
-                    if app.chat_input.bytes().nth(new_cursor.saturating_sub(1)) == Some(b'\n') {
-                        app.input_cursor = new_cursor.saturating_sub(1);
-                    } else {
-                        app.input_cursor = new_cursor;
-                    }
-                } else {
-                    app.input_cursor = new_cursor;
-                }
+        }
+        Action::Backspace => app.composer.backspace(),
+        Action::Delete => app.composer.delete(),
+        Action::CursorLeft => app.composer.left(),
+        Action::CursorRight => app.composer.right(),
+        Action::CursorWordLeft => app.composer.word_left(),
+        Action::CursorWordRight => app.composer.word_right(),
+        Action::DeleteWordBack => app.composer.kill_word_back(),
+        Action::DeleteWordForward => app.composer.kill_word_forward(),
+        Action::KillToStart => app.composer.kill_to_start(),
+        Action::KillToEnd => app.composer.kill_to_end(),
+        Action::Yank => app.composer.yank(),
+        Action::YankPop => app.composer.yank_pop(),
+        Action::Transpose => app.composer.transpose(),
+        Action::Undo => app.composer.undo(),
+        Action::Redo => app.composer.redo(),
+        // these four go by wrapped rows, the editor doesn't know how wide the box is
+        Action::CursorHome => {
+            let text = app.composer.text();
+            let layout = TextLayout::new(text, input_width);
+            let cursor = layout.line_start(layout.cursor_line(app.composer.cursor()));
+            app.composer.set_cursor(cursor);
+        }
+        Action::CursorEnd => {
+            let text = app.composer.text();
+            let layout = TextLayout::new(text, input_width);
+            let line = layout.cursor_line(app.composer.cursor());
+            let cursor = layout.line_end(text, line);
+            app.composer.set_cursor(cursor);
+        }
+        // past the first or last row these walk the sent history instead
+        Action::CursorUp => {
+            let text = app.composer.text();
+            let layout = TextLayout::new(text, input_width);
+            let (cur_line, col) = layout.cursor_position(text, app.composer.cursor());
+            if cur_line > 0 {
+                let cursor = layout.cursor_at(text, cur_line - 1, col);
+                app.composer.set_cursor(cursor);
+            } else if let Some(entry) = app.history.prev(text) {
+                let entry = entry.to_string();
+                app.composer.set_text(&entry);
+            } else {
+                app.composer.set_cursor(0);
             }
-            KeyCode::Enter => {
-                if modifiers.contains(KeyModifiers::SHIFT) {
-                    app.chat_input.insert(app.input_cursor, '\n');
-                    app.input_cursor += '\n'.len_utf8();
-                } else {
-                    let now = Instant::now();
-                    if let Some(last) = app.last_sent {
-                        if now.duration_since(last) < Duration::from_millis(500) {
-                            return;
-                        }
-                    }
-                    let msg = app.chat_input.trim();
-                    if !msg.is_empty() {
-                        let _ = tx.send(msg.to_string());
-                        app.chat_input.clear();
-                        app.input_cursor = 0;
-                        app.last_sent = Some(now);
-                    }
-                }
+        }
+        Action::CursorDown => {
+            let text = app.composer.text();
+            let layout = TextLayout::new(text, input_width);
+            let (cur_line, col) = layout.cursor_position(text, app.composer.cursor());
+            if cur_line + 1 < layout.lines.len() {
+                let cursor = layout.cursor_at(text, cur_line + 1, col);
+                app.composer.set_cursor(cursor);
+            } else if let Some(entry) = app.history.next() {
+                let entry = entry.to_string();
+                app.composer.set_text(&entry);
+            } else {
+                let end = text.len();
+                app.composer.set_cursor(end);
             }
-            KeyCode::Esc => {}
-            KeyCode::Up if modifiers.contains(KeyModifiers::CONTROL) => {
-                if app.chat_scroll > 0 {
-                    app.chat_scroll -= 1;
-                    app.auto_scroll = false;
+        }
+        Action::HistorySearch => {
+            let current = app.composer.text().to_string();
+            app.history.start_search(&current);
+        }
+        Action::SendMessage => {
+            let now = Instant::now();
+            if let Some(last) = app.last_sent {
+                if now.duration_since(last) < Duration::from_millis(500) {
+                    return vec![];
                 }
             }
-            KeyCode::Down if modifiers.contains(KeyModifiers::CONTROL) => {
-                if app.chat_scroll < app.max_scroll {
-                    app.chat_scroll += 1;
-                    app.auto_scroll = false;
-                }
-                if app.chat_scroll >= app.max_scroll {
-                    app.auto_scroll = true;
-                }
+            let max = app.config.max_message_length();
+            let length = app.message_length();
+            if length > max {
+                app.error = Some(format!(
+                    "Message is {length} characters, the limit is {max}"
+                ));
+                app.error_time = Some(now);
+                return vec![];
             }
-            KeyCode::Up => {
-                let lines = split_input_lines(&app.chat_input, input_width);
-                let (cur_line, col) = cursor_line_col(app.input_cursor, &lines);
-                if cur_line > 0 {
-                    let mut new_cursor_byte_idx = 0;
-                    for i in 0..(cur_line - 1) {
-                        new_cursor_byte_idx += lines[i].len()
-                            + if i < lines.len() - 1
-                                && app
-                                    .chat_input
-                                    .bytes()
-                                    .nth(new_cursor_byte_idx + lines[i].len())
-                                    == Some(b'\n')
-                            {
-                                1
-                            } else {
-                                0
-                            };
-                    }
-                    let target_line = &lines[cur_line - 1];
-                    let mut current_visual_width = 0;
-                    let mut char_idx_in_target_line = 0;
-                    for (byte_idx, char_val) in target_line.char_indices() {
-                        let char_display_width = UnicodeWidthChar::width(char_val).unwrap_or(0);
-                        if current_visual_width + char_display_width > col {
-                            break;
-                        }
-                        current_visual_width += char_display_width;
-                        char_idx_in_target_line = byte_idx + char_val.len_utf8();
-                    }
-                    app.input_cursor = new_cursor_byte_idx + char_idx_in_target_line;
-                } else {
-                    app.input_cursor = 0;
-                }
+            let msg = app.composer.text().trim().to_string();
+            let mut effects = remember(app, &msg);
+            if let Some(mut command) = commands::run(app, &msg) {
+                effects.append(&mut command);
+                return effects;
             }
-            KeyCode::Down => {
-                let lines = split_input_lines(&app.chat_input, input_width);
-                let (cur_line, col) = cursor_line_col(app.input_cursor, &lines);
-                if cur_line + 1 < lines.len() {
-                    let mut new_cursor_byte_idx = 0;
-                    for i in 0..(cur_line + 1) {
-                        new_cursor_byte_idx += lines[i].len()
-                            + if i < lines.len() - 1
-                                && app
-                                    .chat_input
-                                    .bytes()
-                                    .nth(new_cursor_byte_idx + lines[i].len())
-                                    == Some(b'\n')
-                            {
-                                1
-                            } else {
-                                0
-                            };
-                    }
-                    let target_line = &lines[cur_line + 1];
-                    let mut current_visual_width = 0;
-                    let mut char_idx_in_target_line = 0;
-                    for (byte_idx, char_val) in target_line.char_indices() {
-                        let char_display_width = UnicodeWidthChar::width(char_val).unwrap_or(0);
-                        if current_visual_width + char_display_width > col {
-                            break;
-                        }
-                        current_visual_width += char_display_width;
-                        char_idx_in_target_line = byte_idx + char_val.len_utf8();
-                    }
-                    app.input_cursor = new_cursor_byte_idx + char_idx_in_target_line;
-                } else {
-                    app.input_cursor = app.chat_input.len();
+            if !msg.is_empty() {
+                app.composer.clear();
+                app.last_sent = Some(now);
+                let msg = commands::unescape(&msg);
+                if remember_emoji(app, msg) {
+                    effects.push(Effect::SaveRecentEmoji(app.recent_emoji.clone()));
                 }
+                effects.push(Effect::SendChat(emoji::expand(msg)));
+                return effects;
             }
-            _ => {}
         }
+        Action::ScrollUp => scroll(app, -1),
+        Action::ScrollDown => scroll(app, 1),
+        Action::ScrollChat(by) => scroll(app, by),
+        Action::Click(ClickTarget::Composer(row, col)) => {
+            let text = app.composer.text();
+            let layout = TextLayout::new(text, input_width);
+            let line =
+                (app.composer_scroll + row as usize).min(layout.lines.len().saturating_sub(1));
+            let cursor = layout.cursor_at(text, line, col as usize);
+            app.composer.set_cursor(cursor);
+        }
+        Action::Click(ClickTarget::User(user)) => app.profile = Some(Profile { user }),
+        _ => {}
+    }
+    vec![]
+}
+
+// shortcodes that went out count as used for the picker's Recent row
+fn remember_emoji(app: &mut App, msg: &str) -> bool {
+    let used = emoji::shortcodes(msg);
+    for (range, _) in used.iter().rev() {
+        emoji::remember(&mut app.recent_emoji, &msg[range.start + 1..range.end - 1]);
+    }
+    !used.is_empty()
+}
+
+// Tab completes the word before the cursor. with more than one match a popup opens, Tab and
+// Shift+Tab go through it, Enter keeps the pick, Esc puts the word back and anything else
+// keeps the pick and then does its thing
+fn complete(app: &mut App, action: Action) -> Option<Action> {
+    let Some(mut completion) = app.completion.take() else {
+        if let Action::Complete | Action::CompletePrev = action {
+            app.completion = completion::start(app);
+            return None;
+        }
+        return Some(action);
+    };
+    match action {
+        Action::Complete => completion.step(app, true),
+        Action::CompletePrev => completion.step(app, false),
+        Action::CancelSearch | Action::VimKey(KeyCode::Esc) => {
+            completion.cancel(app);
+            return None;
+        }
+        Action::SendMessage => return None,
+        action => return Some(action),
+    }
+    app.completion = Some(completion);
+    None
+}
+
+// scrolling back to the bottom turns following new messages back on
+fn scroll(app: &mut App, by: isize) {
+    if by < 0 {
+        app.auto_scroll &= app.chat_scroll == 0;
+    } else if app.chat_scroll < app.max_scroll {
+        app.auto_scroll = false;
+    }
+    app.chat_scroll = app
+        .chat_scroll
+        .saturating_add_signed(by)
+        .min(app.max_scroll);
+    if by > 0 && app.chat_scroll >= app.max_scroll {
+        app.auto_scroll = true;
+    }
+}
+
+// everything sent goes in the history, commands too
+fn remember(app: &mut App, msg: &str) -> Vec<Effect> {
+    if msg.is_empty() {
+        return vec![];
+    }
+    app.history.push(msg.to_string());
+    vec![Effect::SaveHistory {
+        server: app.history.server.clone(),
+        entries: app.history.entries().to_vec(),
+    }]
+}
+
+// while Ctrl+R is on, typing edits the search and the composer shows the match. Enter keeps
+// the match without sending it, Esc goes back to what was there, anything else keeps the
+// match and then does its thing
+fn search(app: &mut App, action: Action) -> Option<Action> {
+    let Some(search) = &app.history.search else {
+        return Some(action);
+    };
+    let found = match action {
+        Action::InsertChar(c) => {
+            let query = format!("{}{}", search.query, c);
+            app.history.search_for(query).map(str::to_string)
+        }
+        Action::Backspace => {
+            let mut query = search.query.clone();
+            query.pop();
+            app.history.search_for(query).map(str::to_string)
+        }
+        Action::HistorySearch => app.history.search_older().map(str::to_string),
+        Action::CancelSearch => {
+            let original = search.original.clone();
+            app.history.search = None;
+            app.composer.set_text(&original);
+            return None;
+        }
+        Action::SendMessage => {
+            app.history.search = None;
+            return None;
+        }
+        action => {
+            app.history.search = None;
+            return Some(action);
+        }
+    };
+    if let Some(found) = found {
+        app.composer.set_text(&found);
     }
+    None
 }
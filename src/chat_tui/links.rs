@@ -0,0 +1,281 @@
+use crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
+    queue,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+};
+use ratatui::{buffer::Buffer, layout::Rect, style::Modifier};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+// a link that ended up on screen, one per wrapped row so a link broken across lines still
+// points to the full url on every piece
+#[derive(Debug, Clone)]
+pub struct Hyperlink {
+    pub area: Rect,
+    pub url: String,
+}
+
+const SCHEMES: [&str; 3] = ["https://", "http://", "www."];
+
+// byte ranges of everything in `text` that looks like a url
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let Some((offset, _)) = SCHEMES
+            .iter()
+            .filter_map(|s| find_ascii_ci(rest, s).map(|i| (i, *s)))
+            .min_by_key(|(i, _)| *i)
+        else {
+            break;
+        };
+        let start = pos + offset;
+
+        // only take it if it starts a word, so "foohttp://" doesn't count
+        let starts_word = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || "([<\"'".contains(c));
+
+        let end = start
+            + text[start..]
+                .find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"')
+                .unwrap_or(text.len() - start);
+        let end = trim_url_end(&text[start..end]) + start;
+
+        let scheme_len = SCHEMES
+            .iter()
+            .find(|s| {
+                text[start..]
+                    .get(..s.len())
+                    .is_some_and(|p| p.eq_ignore_ascii_case(s))
+            })
+            .map_or(0, |s| s.len());
+
+        if starts_word && end > start + scheme_len {
+            urls.push(start..end);
+            pos = end;
+        } else {
+            pos = start + scheme_len.max(1);
+        }
+    }
+    urls
+}
+
+fn find_ascii_ci(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.char_indices().map(|(i, _)| i).find(|&i| {
+        haystack.as_bytes()[i..]
+            .get(..needle.len())
+            .is_some_and(|b| b.eq_ignore_ascii_case(needle.as_bytes()))
+    })
+}
+
+// drop trailing punctuation that's almost always part of the sentence, keeping a closing
+// paren when the url opened one (wikipedia links and friends)
+fn trim_url_end(url: &str) -> usize {
+    let mut end = url.len();
+    while let Some(c) = url[..end].chars().next_back() {
+        let trailing = match c {
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '*' | '_' => true,
+            ')' => url[..end].matches('(').count() < url[..end].matches(')').count(),
+            ']' => url[..end].matches('[').count() < url[..end].matches(']').count(),
+            _ => false,
+        };
+        if !trailing {
+            break;
+        }
+        end -= c.len_utf8();
+    }
+    end
+}
+
+pub fn link_target(url: &str) -> String {
+    if url.to_ascii_lowercase().starts_with("www.") {
+        format!("https://{}", url)
+    } else {
+        url.to_string()
+    }
+}
+
+// nobody agrees on this, so it's a bunch of env sniffing, REETUI_HYPERLINKS=0/1 wins
+pub fn supports_hyperlinks() -> bool {
+    let env = |key: &str| std::env::var(key).unwrap_or_default();
+
+    match env("REETUI_HYPERLINKS").as_str() {
+        "1" | "true" | "yes" | "on" => return true,
+        "0" | "false" | "no" | "off" => return false,
+        _ => {}
+    }
+
+    // tmux and screen eat OSC 8 unless told otherwise
+    if !env("TMUX").is_empty() || env("TERM").starts_with("screen") {
+        return false;
+    }
+    if [
+        "iTerm.app",
+        "WezTerm",
+        "vscode",
+        "ghostty",
+        "Hyper",
+        "Tabby",
+        "rio",
+    ]
+    .contains(&env("TERM_PROGRAM").as_str())
+    {
+        return true;
+    }
+    if [
+        "KITTY_WINDOW_ID",
+        "WT_SESSION",
+        "KONSOLE_VERSION",
+        "WEZTERM_EXECUTABLE",
+    ]
+    .iter()
+    .any(|k| !env(k).is_empty())
+    {
+        return true;
+    }
+    if env("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000) {
+        return true;
+    }
+    let term = env("TERM");
+    ["kitty", "alacritty", "foot", "ghostty", "wezterm"]
+        .iter()
+        .any(|t| term.contains(t))
+}
+
+pub fn open_url(url: &str) -> Result<(), String> {
+    let target = link_target(url);
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut c = Command::new("open");
+        c.arg(&target);
+        c
+    } else if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", "start", "", &target]);
+        c
+    } else {
+        let mut c = Command::new("xdg-open");
+        c.arg(&target);
+        c
+    };
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Couldn't open {}: {e}", target))
+}
+
+// ratatui can't carry OSC 8 in cells (it counts the escape bytes as width), so after a frame
+// is flushed we rewrite the link cells ourselves with the hyperlink wrapped around them
+pub fn write_hyperlinks<W: Write>(
+    out: &mut W,
+    buffer: &Buffer,
+    links: &[Hyperlink],
+) -> io::Result<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for link in links {
+        let area = link.area.intersection(buffer.area);
+        if area.is_empty() {
+            continue;
+        }
+        let target = link_target(&link.url);
+        queue!(
+            out,
+            MoveTo(area.x, area.y),
+            Print(format!("\x1b]8;;{}\x1b\\", target))
+        )?;
+        let mut to_skip = 0;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, area.y)];
+            if to_skip > 0 {
+                to_skip -= 1;
+                continue;
+            }
+            queue!(
+                out,
+                SetAttribute(Attribute::Reset),
+                SetForegroundColor(cell.fg.into()),
+                SetBackgroundColor(cell.bg.into())
+            )?;
+            for (modifier, attr) in [
+                (Modifier::BOLD, Attribute::Bold),
+                (Modifier::DIM, Attribute::Dim),
+                (Modifier::ITALIC, Attribute::Italic),
+                (Modifier::UNDERLINED, Attribute::Underlined),
+                (Modifier::REVERSED, Attribute::Reverse),
+            ] {
+                if cell.modifier.contains(modifier) {
+                    queue!(out, SetAttribute(attr))?;
+                }
+            }
+            queue!(out, Print(cell.symbol()))?;
+            to_skip = unicode_width::UnicodeWidthStr::width(cell.symbol()).saturating_sub(1);
+        }
+        queue!(out, Print("\x1b]8;;\x1b\\"), SetAttribute(Attribute::Reset))?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn trailing_punctuation_is_the_sentence() {
+        assert_eq!(urls("see https://a.com."), ["https://a.com"]);
+        assert_eq!(urls("https://a.com/x?, then"), ["https://a.com/x"]);
+        assert_eq!(urls("'https://a.com/q?a=1'!"), ["https://a.com/q?a=1"]);
+        assert_eq!(urls("www.a.com:"), ["www.a.com"]);
+    }
+
+    #[test]
+    fn parens_only_when_the_url_opened_one() {
+        assert_eq!(
+            urls("https://en.wikipedia.org/wiki/Rust_(language)"),
+            ["https://en.wikipedia.org/wiki/Rust_(language)"]
+        );
+        assert_eq!(urls("(see https://a.com/x)"), ["https://a.com/x"]);
+        assert_eq!(urls("[https://a.com]"), ["https://a.com"]);
+        assert_eq!(urls("<https://a.com>"), ["https://a.com"]);
+    }
+
+    #[test]
+    fn schemes_in_any_case() {
+        assert_eq!(
+            urls("HTTPS://A.COM and Www.b.org"),
+            ["HTTPS://A.COM", "Www.b.org"]
+        );
+        assert_eq!(link_target("WWW.a.com"), "https://WWW.a.com");
+        assert_eq!(link_target("http://a.com"), "http://a.com");
+    }
+
+    #[test]
+    fn not_a_url() {
+        assert!(urls("foohttp://a.com").is_empty());
+        assert!(urls("https://").is_empty());
+        assert!(urls("www.").is_empty());
+        assert!(urls("plain text").is_empty());
+        assert_eq!(urls("https:// https://a.com"), ["https://a.com"]);
+    }
+
+    #[test]
+    fn several_and_multibyte() {
+        assert_eq!(
+            urls("é https://a.com/é, http://b.com\nwww.c.com"),
+            ["https://a.com/é", "http://b.com", "www.c.com"]
+        );
+    }
+}
@@ -1,134 +1,90 @@
 use ratatui::prelude::Rect;
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Margin},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
     Frame,
 };
-use unicode_width::UnicodeWidthStr;
 
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::action::ClickTarget;
 use crate::app::App;
-use crate::chat_tui::utils::wrap_with_prefixes;
+use crate::completion;
+use crate::emoji;
+use crate::glyphs::{display_icon, GlyphSet};
+use crate::text_layout::{expand_tabs, str_width, str_width_from, TextLayout};
+use crate::theme::Theme;
 
+pub use self::cache::MessageLayoutCache;
 pub use self::data::ChatMessage;
-pub use self::events::handle_event;
-pub use self::utils::{cursor_line_col, get_theme, relative_time, rgb_to_color, split_input_lines};
-pub use self::websocket::start_ws_thread;
+pub use self::events::{map_event, update};
+pub use self::links::{find_urls, open_url, supports_hyperlinks, write_hyperlinks, Hyperlink};
+pub use self::utils::{relative_time, relative_time_next_change};
+pub use self::websocket::{spawn_ws_task, WS_URL};
 
+mod cache;
 mod data;
 mod events;
+mod links;
 mod utils;
 mod websocket;
 // also known as wesock
 
+// the composer grows up to this many rows, then scrolls
+const COMPOSER_MAX_ROWS: usize = 6;
+
 // this function draw the whole freaking thing
-pub fn ui(f: &mut Frame, app: &mut App, chat_messages: &[ChatMessage]) {
-    let theme = get_theme();
+pub fn ui(f: &mut Frame, app: &mut App) {
+    let theme = app.theme.clone();
+    let glyphs = app.glyphs();
+    let glyph_set = app.glyph_set;
     let area = f.area();
 
-    let input_width = area.width as usize;
-    let input_lines_for_height_calc =
-        split_input_lines(&app.chat_input, input_width.saturating_sub(2));
-    let input_height = input_lines_for_height_calc.len().max(1).min(6) as u16 + 2;
+    // borders take two columns and the cursor needs one past the last character
+    app.input_width = (area.width as usize).saturating_sub(3);
+    let input_layout = TextLayout::new(app.composer.text(), app.input_width);
+    let input_rows = input_layout.lines.len().clamp(1, COMPOSER_MAX_ROWS);
+    let input_height = input_rows as u16 + 2;
 
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(3), Constraint::Length(input_height)])
         .split(area);
 
-    let mut chat_lines = Vec::new();
     let chat_area_width_for_content = layout[0].width.saturating_sub(2) as usize;
-
-    let mut last_user: Option<String> = None;
-
-    for msg in chat_messages {
-        let is_same_user = last_user.as_ref().map_or(false, |u| u == &msg.user);
-
-        let timestamp_str = msg
-            .timestamp
-            .map(|t| relative_time(t))
-            .unwrap_or_else(|| " ".to_string());
-
-        let ts_span = Span::styled(
-            timestamp_str,
-            Style::default()
-                .fg(Color::DarkGray)
-                .add_modifier(Modifier::DIM | Modifier::ITALIC),
-        );
-
-        if !is_same_user {
-            let l_top = Span::styled("┌ ", Style::default().fg(Color::DarkGray));
-            let icon = msg.icon.clone().unwrap_or_else(|| "󰬌".to_string());
-            let icon_span_str = format!("{} ", icon);
-            let user_str = &msg.user;
-
-            let fixed_prefix_width = UnicodeWidthStr::width("┌ ");
-            let icon_user_width = UnicodeWidthStr::width(icon_span_str.as_str())
-                + UnicodeWidthStr::width(user_str.as_str());
-
-            let mut header_spans = vec![
-                l_top,
-                Span::styled(
-                    icon_span_str,
-                    Style::default().fg(rgb_to_color(&theme.button_focus)),
-                ),
-                Span::styled(
-                    user_str,
-                    Style::default()
-                        .fg(rgb_to_color(&theme.button_focus))
-                        .add_modifier(Modifier::BOLD),
-                ),
-            ];
-
-            let header_content_current_width = fixed_prefix_width + icon_user_width;
-            let needed_padding_width = chat_area_width_for_content
-                .saturating_sub(header_content_current_width)
-                .saturating_sub(ts_span.width())
-                .saturating_sub(1);
-
-            if needed_padding_width > 0 {
-                header_spans.push(Span::raw(" ".repeat(needed_padding_width)));
-            } else {
-                if chat_area_width_for_content > header_content_current_width + ts_span.width() {
-                    header_spans.push(Span::raw(" "));
-                }
-            }
-            header_spans.push(ts_span);
-
-            chat_lines.push(Line::from(header_spans));
-
-            let wrapped_content = wrap_with_prefixes(
-                &msg.content,
-                chat_area_width_for_content,
-                "│ ",
-                Style::default().fg(Color::DarkGray),
-                Style::default().fg(rgb_to_color(&theme.text)),
-            );
-            chat_lines.extend(wrapped_content);
-        } else {
-            let wrapped_content = wrap_with_prefixes(
-                &msg.content,
-                chat_area_width_for_content,
-                "│ ",
-                Style::default().fg(Color::DarkGray),
-                Style::default().fg(rgb_to_color(&theme.text)),
-            );
-            chat_lines.extend(wrapped_content);
-        }
-        last_user = Some(msg.user.clone());
-    }
+    app.message_cache.sync(
+        &app.chat_messages,
+        chat_area_width_for_content,
+        glyphs,
+        app.theme_generation,
+        !app.hyperlinks_supported,
+    );
 
     let phantom_lines = 2;
-    for _ in 0..phantom_lines {
-        chat_lines.push(Line::raw(""));
-    }
-
     let chat_area_height = layout[0].height;
-    let total_lines = chat_lines.len();
     let visible_lines = chat_area_height as usize;
+    if app.auto_scroll {
+        // wrap the tail for real first, so following the bottom isn't thrown off by guesses
+        let mut rows = 0;
+        for i in (0..app.chat_messages.len()).rev() {
+            if rows >= visible_lines {
+                break;
+            }
+            let header = app.message_cache.header(i) as usize;
+            let cached = app
+                .message_cache
+                .layout(i, &app.chat_messages[i], &theme, glyphs);
+            rows += header + cached.lines.len();
+        }
+    }
+    let total_lines = app.message_cache.total_rows() + phantom_lines;
     let max_scroll = if visible_lines > phantom_lines {
-        total_lines.saturating_sub(visible_lines - phantom_lines) as u16
+        total_lines.saturating_sub(visible_lines - phantom_lines)
     } else {
         0
     };
@@ -137,50 +93,342 @@ pub fn ui(f: &mut Frame, app: &mut App, chat_messages: &[ChatMessage]) {
     }
     app.max_scroll = max_scroll;
 
-    let visible_chat_lines = chat_lines
-        .iter()
-        .skip(app.chat_scroll as usize)
-        .take(visible_lines)
-        .cloned()
-        .collect::<Vec<_>>();
+    let content_area = Rect {
+        x: layout[0].x + 1,
+        y: layout[0].y + 1,
+        width: layout[0].width.saturating_sub(2),
+        height: layout[0].height.saturating_sub(2),
+    };
 
-    let chat_block = Block::default()
+    // only the messages that overlap the window get wrapped and drawn
+    let window = app.chat_scroll..app.chat_scroll + visible_lines;
+    let mut visible_chat_lines: Vec<Line> = Vec::with_capacity(visible_lines);
+    let mut hyperlinks = Vec::new();
+    let mut click_areas = Vec::new();
+    for i in app.message_cache.message_at(window.start)..app.chat_messages.len() {
+        let mut line_no = app.message_cache.start(i);
+        if line_no >= window.end {
+            break;
+        }
+        let msg = &app.chat_messages[i];
+        if app.message_cache.header(i) {
+            let row = line_no.wrapping_sub(window.start);
+            if !msg.system && row < content_area.height as usize {
+                // the icon and the name, clicking them opens the profile
+                let name = format!("{} {}", header_icon(msg, glyph_set), msg.user);
+                click_areas.push((
+                    Rect {
+                        x: content_area.x + str_width(glyphs.header_prefix) as u16,
+                        y: content_area.y + row as u16,
+                        width: str_width(&name) as u16,
+                        height: 1,
+                    }
+                    .intersection(content_area),
+                    ClickTarget::User(msg.user.clone()),
+                ));
+            }
+            if (window.start..window.end).contains(&line_no) {
+                visible_chat_lines.push(header_line(
+                    msg,
+                    chat_area_width_for_content,
+                    &theme,
+                    glyph_set,
+                ));
+            }
+            line_no += 1;
+        }
+        let cached = app.message_cache.layout(i, msg, &theme, glyphs);
+        for (i, line) in cached.lines.iter().enumerate() {
+            if (window.start..window.end).contains(&(line_no + i)) {
+                visible_chat_lines.push(line.clone());
+            }
+        }
+        for r in &cached.links {
+            let line = line_no + r.line;
+            if line < window.start || line - window.start >= content_area.height as usize {
+                continue;
+            }
+            let area = Rect {
+                x: content_area.x + r.col as u16,
+                y: content_area.y + (line - window.start) as u16,
+                width: r.width as u16,
+                height: 1,
+            }
+            .intersection(content_area);
+            if !area.is_empty() {
+                hyperlinks.push(Hyperlink {
+                    area,
+                    url: r.url.clone(),
+                });
+            }
+        }
+    }
+    app.hyperlinks = hyperlinks;
+    app.timestamp_refresh =
+        next_timestamp_change(&app.chat_messages).map(|wait| Instant::now() + wait);
+
+    let mut chat_block = Block::default()
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
+        .border_set(glyphs.border)
         .title("Chat")
-        .border_style(Style::default().fg(rgb_to_color(&theme.border)));
-    let chat_box = Paragraph::new(visible_chat_lines)
-        .block(chat_block)
-        .wrap(Wrap { trim: false });
+        .border_style(Style::default().fg(theme.border));
+    if let Some(ref err) = app.error {
+        chat_block = chat_block
+            .title(Line::from(Span::styled(format!(" {} ", err), theme.error())).right_aligned());
+    }
+    let chat_box = Paragraph::new(visible_chat_lines).block(chat_block);
     f.render_widget(chat_box, layout[0]);
 
-    let input_block = Block::default()
+    let input_title = match &app.history.search {
+        Some(search) if search.found.is_none() && !search.query.is_empty() => {
+            format!("failing search: {}", search.query)
+        }
+        Some(search) => format!("search: {}", search.query),
+        None => match &app.vim {
+            Some(vim) => format!("Message {}", vim.indicator()),
+            None => "Message".to_string(),
+        },
+    };
+    let mut input_block = Block::default()
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .title("Message")
-        .border_style(Style::default().fg(rgb_to_color(&theme.border_focus)));
+        .border_set(glyphs.border)
+        .title(input_title)
+        .border_style(Style::default().fg(theme.border_focus));
+    if let Some(key) = app.newline_key() {
+        input_block = input_block.title(
+            Line::from(Span::styled(
+                format!(" {key}: newline "),
+                Style::default().fg(theme.hint),
+            ))
+            .right_aligned(),
+        );
+    }
+    // what the :shortcodes: in the draft will turn into once it's sent
+    let mut preview: Vec<String> = Vec::new();
+    let text = app.composer.text();
+    for (range, emoji) in emoji::shortcodes(text) {
+        let shown = format!("{} {}", emoji, &text[range]);
+        if !preview.contains(&shown) {
+            preview.push(shown);
+        }
+    }
+    // how much of the limit the message uses, only once there's something to count
+    if !text.is_empty() {
+        let length = app.message_length();
+        let max = app.config.max_message_length();
+        let lines = text.split('\n').count();
+        let mut counter = format!(" {length}/{max} ");
+        if lines > 1 {
+            counter = format!(" {length}/{max}, {lines} lines ");
+        }
+        let style = if length > max {
+            theme.error()
+        } else {
+            Style::default().fg(theme.hint)
+        };
+        input_block = input_block.title_bottom(Line::from(Span::styled(counter, style)));
+    }
+    if !preview.is_empty() {
+        input_block = input_block.title_bottom(
+            Line::from(Span::styled(
+                format!(" {} ", preview.join("  ")),
+                Style::default().fg(theme.hint),
+            ))
+            .right_aligned(),
+        );
+    }
 
-    let (cursor_line, cursor_col) = cursor_line_col(app.input_cursor, &input_lines_for_height_calc);
-    let input_para = Paragraph::new(app.chat_input.as_str())
+    let (cursor_line, cursor_col) =
+        input_layout.cursor_position(app.composer.text(), app.composer.cursor());
+    let selection = app
+        .vim
+        .as_ref()
+        .and_then(|vim| vim.selection(&app.composer));
+    let selected = theme.focused(theme.border_focus);
+    let input_lines: Vec<Line> = input_layout
+        .lines
+        .iter()
+        .map(|l| composer_line(app.composer.text(), l.start..l.end, &selection, selected))
+        .collect();
+    // only as far as it takes to bring the cursor back in view, so the text doesn't jump
+    let total_rows = input_layout.lines.len();
+    let mut scroll = app.composer_scroll;
+    if cursor_line < scroll {
+        scroll = cursor_line;
+    } else if cursor_line >= scroll + input_rows {
+        scroll = cursor_line + 1 - input_rows;
+    }
+    scroll = scroll.min(total_rows.saturating_sub(input_rows));
+    app.composer_scroll = scroll;
+
+    let input_area = Rect {
+        x: layout[1].x,
+        y: layout[1].y,
+        width: layout[1].width,
+        height: input_height,
+    };
+    let input_para = Paragraph::new(input_lines)
         .block(input_block)
-        .wrap(Wrap { trim: false })
-        .style(Style::default().fg(rgb_to_color(&theme.text)))
-        .scroll((cursor_line as u16, 0));
-
-    f.render_widget(
-        input_para,
-        Rect {
-            x: layout[1].x,
-            y: layout[1].y,
-            width: layout[1].width,
-            height: input_height,
-        },
-    );
+        .style(Style::default().fg(theme.text))
+        .scroll((scroll as u16, 0));
+    f.render_widget(input_para, input_area);
+    // the row and column get filled in when the click comes
+    click_areas.push((
+        input_area.inner(Margin {
+            vertical: 1,
+            horizontal: 1,
+        }),
+        ClickTarget::Composer(0, 0),
+    ));
+    app.click_areas = click_areas;
+
+    if total_rows > input_rows {
+        let mut state = ScrollbarState::new(total_rows - input_rows + 1)
+            .position(scroll)
+            .viewport_content_length(input_rows);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .symbols(glyphs.scrollbar.clone())
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(Style::default().fg(theme.border_focus)),
+            input_area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut state,
+        );
+    }
 
     if app.cursor_tick_state {
         let cursor_x = layout[1].x + (cursor_col as u16) + 1;
-        let cursor_y = layout[1].y + (cursor_line as u16) + 1;
+        let cursor_y = layout[1].y + (cursor_line - scroll) as u16 + 1;
 
         f.set_cursor_position((cursor_x, cursor_y));
     }
+    completion::ui(f, app, layout[1]);
+}
+
+// one row of the composer, with the vim visual selection picked out
+fn composer_line(
+    text: &str,
+    row: Range<usize>,
+    selection: &Option<Range<usize>>,
+    selected: Style,
+) -> Line<'static> {
+    let Some(sel) = selection
+        .as_ref()
+        .filter(|s| s.start < row.end && s.end > row.start)
+    else {
+        return Line::raw(expand_tabs(&text[row], 0).into_owned());
+    };
+    let cuts = [
+        row.start,
+        sel.start.max(row.start),
+        sel.end.min(row.end),
+        row.end,
+    ];
+    let mut col = 0;
+    let spans = cuts
+        .windows(2)
+        .enumerate()
+        .map(|(i, w)| {
+            let piece = &text[w[0]..w[1]];
+            let span = Span::raw(expand_tabs(piece, col).into_owned());
+            col += str_width_from(piece, col);
+            if i == 1 {
+                span.style(selected)
+            } else {
+                span
+            }
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+fn header_icon(msg: &ChatMessage, set: GlyphSet) -> &str {
+    let glyphs = set.glyphs();
+    match &msg.icon {
+        _ if msg.system => glyphs.system_icon,
+        Some(icon) => display_icon(icon, set),
+        None => glyphs.avatar,
+    }
+}
+
+fn header_line(msg: &ChatMessage, width: usize, theme: &Theme, set: GlyphSet) -> Line<'static> {
+    let glyphs = set.glyphs();
+    let timestamp_str = msg
+        .timestamp
+        .map(relative_time)
+        .unwrap_or_else(|| " ".to_string());
+
+    let ts_span = Span::styled(
+        timestamp_str,
+        Style::default()
+            .fg(theme.muted)
+            .add_modifier(Modifier::DIM | Modifier::ITALIC),
+    );
+
+    let l_top = Span::styled(glyphs.header_prefix, Style::default().fg(theme.muted));
+    let icon_span_str = format!("{} ", header_icon(msg, set));
+    let user_str = msg.user.clone();
+
+    let fixed_prefix_width = str_width(glyphs.header_prefix);
+    let icon_user_width = str_width(&icon_span_str) + str_width(&user_str);
+
+    let mut header_spans = vec![
+        l_top,
+        Span::styled(icon_span_str, Style::default().fg(theme.button_focus)),
+        Span::styled(
+            user_str,
+            Style::default()
+                .fg(theme.button_focus)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ];
+
+    let header_content_current_width = fixed_prefix_width + icon_user_width;
+    let needed_padding_width = width
+        .saturating_sub(header_content_current_width)
+        .saturating_sub(ts_span.width())
+        .saturating_sub(1);
+
+    if needed_padding_width > 0 {
+        header_spans.push(Span::raw(" ".repeat(needed_padding_width)));
+    } else if width > header_content_current_width + ts_span.width() {
+        header_spans.push(Span::raw(" "));
+    }
+    header_spans.push(ts_span);
+
+    Line::from(header_spans)
+}
+
+// the soonest any timestamp in the history rolls over, only the last day can still change
+pub fn next_timestamp_change(chat_messages: &VecDeque<ChatMessage>) -> Option<Duration> {
+    let now = Utc::now().timestamp();
+    chat_messages
+        .iter()
+        .rev()
+        .filter_map(|m| m.timestamp)
+        .take_while(|&ts| now - ts < 86400)
+        .filter_map(|ts| relative_time_next_change(ts, now))
+        .min()
+        .map(|secs| Duration::from_secs(secs as u64))
+}
+
+// links are numbered as messages arrive, so [n] keeps pointing at the same link even after
+// older messages fall out of the history
+pub fn nth_link(chat_messages: &VecDeque<ChatMessage>, n: usize) -> Option<String> {
+    let msg = chat_messages.iter().rev().find(|m| m.link_base < n)?;
+    links::find_urls(&msg.content)
+        .get(n - msg.link_base - 1)
+        .map(|r| msg.content[r.clone()].to_string())
+}
+
+pub fn last_link(chat_messages: &VecDeque<ChatMessage>) -> Option<String> {
+    chat_messages.iter().rev().find_map(|msg| {
+        links::find_urls(&msg.content)
+            .pop()
+            .map(|r| msg.content[r].to_string())
+    })
 }
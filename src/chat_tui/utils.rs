@@ -1,26 +1,15 @@
 use chrono::{Local, TimeZone, Utc};
 use ratatui::{
-    style::{Color, Style},
+    style::Style,
     text::{Line, Span},
 };
-use std::path::PathBuf;
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use std::borrow::Cow;
 
-use super::data::{Rgb, Theme};
+use crate::text_layout::{expand_tabs, str_width, TextLayout};
 
 // this file have some utils, ill soon make the auth tui and the home tui in this, and rename it
 // app, or maiw
 
-pub fn rgb_to_color(rgb: &Rgb) -> Color {
-    Color::Rgb(rgb.0, rgb.1, rgb.2)
-}
-
-pub fn get_theme() -> Theme {
-    let theme_path = PathBuf::from("theme.json");
-    let data = std::fs::read_to_string(theme_path).expect("theme.json not found");
-    serde_json::from_str(&data).expect("Invalid theme.json")
-}
-
 pub fn relative_time(ts: i64) -> String {
     let now = Utc::now().timestamp();
     let diff = now - ts;
@@ -37,116 +26,79 @@ pub fn relative_time(ts: i64) -> String {
     }
 }
 
-pub fn wrap_with_prefixes<'a>(
-    content: &'a str,
-    width: usize,
-    prefix: &str,
-    prefix_style: Style,
-    content_style: Style,
-) -> Vec<Line<'a>> {
-    let mut lines = Vec::new();
-    let prefix_display_width = UnicodeWidthStr::width(prefix);
-    let available_content_width = width.saturating_sub(prefix_display_width);
-
-    if content.is_empty() {
-        lines.push(Line::from(vec![
-            Span::styled(prefix.to_string(), prefix_style),
-            Span::raw(""),
-        ]));
-        return lines;
-    }
-
-    let mut current_segment_start_byte_idx = 0;
-
-    for (char_idx, char_val) in content.char_indices() {
-        if char_val == '\n' {
-            let segment = &content[current_segment_start_byte_idx..char_idx];
-            let prefixed_line = Line::from(vec![
-                Span::styled(prefix.to_string(), prefix_style),
-                Span::styled(segment.to_string(), content_style),
-            ]);
-            lines.push(prefixed_line);
-            current_segment_start_byte_idx = char_idx + char_val.len_utf8();
-            continue;
-        }
-
-        let potential_segment =
-            &content[current_segment_start_byte_idx..char_idx + char_val.len_utf8()];
-        let potential_display_width = UnicodeWidthStr::width(potential_segment);
+// a run of message text with one style, links carry the url they point to
+pub struct Segment<'a> {
+    pub text: Cow<'a, str>,
+    pub style: Style,
+    pub url: Option<&'a str>,
+}
 
-        if potential_display_width > available_content_width {
-            let segment = &content[current_segment_start_byte_idx..char_idx];
-            let prefixed_line = Line::from(vec![
-                Span::styled(prefix.to_string(), prefix_style),
-                Span::styled(segment.to_string(), content_style),
-            ]);
-            lines.push(prefixed_line);
-            current_segment_start_byte_idx = char_idx;
-        }
-    }
+// where a piece of link landed after wrapping, col counts from the start of the line
+// (prefix included)
+pub struct LinkRegion {
+    pub line: usize,
+    pub col: usize,
+    pub width: usize,
+    pub url: String,
+}
 
-    if current_segment_start_byte_idx < content.len() {
-        let segment = &content[current_segment_start_byte_idx..];
-        let prefixed_line = Line::from(vec![
-            Span::styled(prefix.to_string(), prefix_style),
-            Span::styled(segment.to_string(), content_style),
-        ]);
-        lines.push(prefixed_line);
+// how long until relative_time(ts) shows something else, None once it's a fixed HH:MM
+pub fn relative_time_next_change(ts: i64, now: i64) -> Option<i64> {
+    let diff = now - ts;
+    match diff {
+        d if d < 0 => Some(-d + 1),
+        0..=59 => Some(1),
+        60..=3599 => Some(60 - diff % 60),
+        3600..=86399 => Some((3600 - diff % 3600).min(86400 - diff)),
+        _ => None,
     }
-
-    lines
 }
-pub fn split_input_lines(input: &str, width: usize) -> Vec<String> {
-    let mut lines = vec![];
-    let mut current_byte_offset = 0;
-    let mut current_line_display_width = 0;
-
-    for (byte_idx, char_val) in input.char_indices() {
-        let char_display_width = UnicodeWidthChar::width(char_val).unwrap_or(0);
 
-        if current_line_display_width + char_display_width > width || char_val == '\n' {
-            lines.push(input[current_byte_offset..byte_idx].to_string());
-
-            current_byte_offset = byte_idx
-                + if char_val == '\n' {
-                    char_val.len_utf8()
-                } else {
-                    0
-                };
-            current_line_display_width = if char_val == '\n' {
-                0
-            } else {
-                char_display_width
-            };
-        } else {
-            current_line_display_width += char_display_width;
-        }
-    }
-    lines.push(input[current_byte_offset..].to_string());
+pub fn wrap_with_prefixes(
+    segments: &[Segment],
+    width: usize,
+    prefix: &str,
+    prefix_style: Style,
+) -> (Vec<Line<'static>>, Vec<LinkRegion>) {
+    let prefix_display_width = str_width(prefix);
+    let available_content_width = width.saturating_sub(prefix_display_width);
 
-    if lines
-        .last()
-        .map_or(false, |l| l.is_empty() && input.ends_with('\n'))
-    {
-        lines.pop();
+    // lay the whole message out as one string, then cut every row back into styled pieces
+    let mut content = String::new();
+    let mut bounds = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let start = content.len();
+        content.push_str(&segment.text);
+        bounds.push(start..content.len());
     }
-    lines
-}
-
-pub fn cursor_line_col(cursor_byte_idx: usize, lines: &[String]) -> (usize, usize) {
-    let mut current_byte_position = 0;
-    for (line_idx, line_str) in lines.iter().enumerate() {
-        let line_byte_len = line_str.len();
-        if cursor_byte_idx >= current_byte_position
-            && cursor_byte_idx <= current_byte_position + line_byte_len
-        {
-            let offset_in_line_bytes = cursor_byte_idx - current_byte_position;
-            let col = line_str[..offset_in_line_bytes].width();
-            return (line_idx, col);
+    let layout = TextLayout::new(&content, available_content_width);
+
+    let mut lines = Vec::with_capacity(layout.lines.len());
+    let mut links = Vec::new();
+    for (line_idx, row) in layout.lines.iter().enumerate() {
+        let mut spans = vec![Span::styled(prefix.to_string(), prefix_style)];
+        let mut col = 0;
+        for (segment, range) in segments.iter().zip(&bounds) {
+            let start = range.start.max(row.start);
+            let end = range.end.min(row.end);
+            if start >= end {
+                continue;
+            }
+            let piece = expand_tabs(&content[start..end], col);
+            let piece_width = str_width(&piece);
+            if let Some(url) = segment.url {
+                links.push(LinkRegion {
+                    line: line_idx,
+                    col: prefix_display_width + col,
+                    width: piece_width,
+                    url: url.to_string(),
+                });
+            }
+            col += piece_width;
+            spans.push(Span::styled(piece.into_owned(), segment.style));
         }
-        current_byte_position += line_byte_len;
+        lines.push(Line::from(spans));
     }
-    let last_line_idx = lines.len().saturating_sub(1);
-    let last_line_visual_width = lines.last().map_or(0, |l| l.width());
-    (last_line_idx, last_line_visual_width)
+
+    (lines, links)
 }
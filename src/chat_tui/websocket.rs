@@ -1,53 +1,61 @@
 use chrono::Local;
 use futures_util::{SinkExt, StreamExt};
-use std::thread;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
 use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
 
 use super::data::ChatMessage;
+use crate::action::AppEvent;
 
 // isock when trying to host smt
 //  ):
 
-pub fn start_ws_thread(
+pub const WS_URL: &str = "ws://isock.reetui.hackclub.app";
+
+pub fn spawn_ws_task(
     ws_url: String,
     token: String,
-    chat_tx: std::sync::mpsc::Sender<ChatMessage>,
-    mut send_rx: tokio::sync::mpsc::UnboundedReceiver<String>,
-) {
-    thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-        rt.block_on(async move {
-            let (ws_stream, _) = connect_async(&ws_url).await.expect("Failed to connect WS");
-            let (mut ws_write, mut ws_read) = ws_stream.split();
+    events: UnboundedSender<AppEvent>,
+    mut send_rx: UnboundedReceiver<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let ws_stream = match connect_async(&ws_url).await {
+            Ok((ws_stream, _)) => ws_stream,
+            Err(e) => {
+                let _ = events.send(AppEvent::Error(format!("Failed to connect: {e}")));
+                return;
+            }
+        };
+        let (mut ws_write, mut ws_read) = ws_stream.split();
 
-            ws_write
-                .send(WsMessage::Text(token.into()))
-                .await
-                .expect("Failed to send token");
+        if let Err(e) = ws_write.send(WsMessage::Text(token.into())).await {
+            let _ = events.send(AppEvent::Error(format!("Failed to send token: {e}")));
+            return;
+        }
 
-            let send_fut = tokio::spawn(async move {
-                while let Some(msg) = send_rx.recv().await {
-                    if !msg.trim().is_empty() {
-                        let _ = ws_write.send(WsMessage::Text(msg.into())).await;
-                    }
+        let send_fut = tokio::spawn(async move {
+            while let Some(msg) = send_rx.recv().await {
+                if !msg.trim().is_empty() {
+                    let _ = ws_write.send(WsMessage::Text(msg.into())).await;
                 }
-            });
+            }
+        });
 
-            while let Some(msg) = ws_read.next().await {
-                if let Ok(WsMessage::Text(txt)) = msg {
-                    if let Ok(parsed) = serde_json::from_str::<ChatMessage>(&txt) {
-                        let _ = chat_tx.send(parsed);
-                    } else {
-                        let _ = chat_tx.send(ChatMessage {
-                            user: "system".to_string(),
-                            content: txt.to_string(),
-                            icon: Some("󰚩".to_string()),
-                            timestamp: Some(Local::now().timestamp()),
-                        });
-                    }
+        while let Some(msg) = ws_read.next().await {
+            if let Ok(WsMessage::Text(txt)) = msg {
+                let parsed =
+                    serde_json::from_str::<ChatMessage>(&txt).unwrap_or_else(|_| ChatMessage {
+                        user: "system".to_string(),
+                        content: txt.to_string(),
+                        system: true,
+                        timestamp: Some(Local::now().timestamp()),
+                        ..Default::default()
+                    });
+                if events.send(AppEvent::ChatMessage(parsed)).is_err() {
+                    break;
                 }
             }
-            let _ = send_fut.await;
-        });
-    });
+        }
+        send_fut.abort();
+    })
 }
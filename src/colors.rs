@@ -0,0 +1,272 @@
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+// how many colors the terminal can show, themes are written in rgb and get squashed down to
+// whatever this says before anything is drawn
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    #[default]
+    #[serde(rename = "truecolor")]
+    TrueColor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+    // NO_COLOR, only bold/reverse/underline are left to tell things apart
+    #[serde(rename = "none")]
+    None,
+}
+
+impl ColorDepth {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorDepth::TrueColor => "truecolor",
+            ColorDepth::Ansi256 => "256",
+            ColorDepth::Ansi16 => "16",
+            ColorDepth::None => "none",
+        }
+    }
+}
+
+// the config wins, then NO_COLOR (https://no-color.org), then guessing from the environment.
+// multiplexers pass truecolor through only when they say so in COLORTERM, so a bare
+// screen/tmux TERM counts as 256
+pub fn detect(config: Option<ColorDepth>) -> ColorDepth {
+    detect_from(config, |name| std::env::var(name).unwrap_or_default())
+}
+
+// detect with the environment passed in, `var` gives "" for anything unset
+fn detect_from(config: Option<ColorDepth>, var: impl Fn(&str) -> String) -> ColorDepth {
+    if let Some(depth) = config {
+        return depth;
+    }
+    if !var("NO_COLOR").is_empty() {
+        return ColorDepth::None;
+    }
+    let colorterm = var("COLORTERM").to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorDepth::TrueColor;
+    }
+    let term = var("TERM").to_lowercase();
+    if term == "dumb" {
+        return ColorDepth::None;
+    }
+    if ["truecolor", "24bit", "direct"]
+        .iter()
+        .any(|t| term.contains(t))
+    {
+        return ColorDepth::TrueColor;
+    }
+    if term.starts_with("screen") || term.starts_with("tmux") || term.contains("256color") {
+        return ColorDepth::Ansi256;
+    }
+    let term_program = var("TERM_PROGRAM");
+    if !var("WT_SESSION").is_empty()
+        || ["iTerm.app", "WezTerm", "vscode", "ghostty"].contains(&term_program.as_str())
+    {
+        return ColorDepth::TrueColor;
+    }
+    ColorDepth::Ansi16
+}
+
+// the usual xterm values for the 16 ansi colors, in palette order
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs();
+    // weighted so greens don't swallow everything, close enough without going to lab
+    2 * d(r1, r2).pow(2) + 4 * d(g1, g2).pow(2) + 3 * d(b1, b2).pow(2)
+}
+
+// rgb for a 256 palette index
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI16[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + 10 * (i - 232);
+            (v, v, v)
+        }
+    }
+}
+
+// nearest entry out of the 6x6x6 cube and the 24 step gray ramp, the first 16 are left
+// alone since every terminal remaps them to its own scheme
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let closest_level = |v: u8| {
+        (0..6)
+            .min_by_key(|&n| (CUBE_LEVELS[n] as i32 - v as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * closest_level(rgb.0) + 6 * closest_level(rgb.1) + closest_level(rgb.2);
+    let avg = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + (avg.saturating_sub(3) / 10).min(23) as u8;
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, c)| distance(rgb, *c))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+pub fn quantize(color: Color, depth: ColorDepth) -> Color {
+    match (depth, color) {
+        (ColorDepth::None, _) => Color::Reset,
+        (ColorDepth::TrueColor, c) => c,
+        (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_256((r, g, b))),
+        (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => nearest_16((r, g, b)),
+        (ColorDepth::Ansi16, Color::Indexed(i)) => nearest_16(indexed_rgb(i)),
+        (_, c) => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> String {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map_or(String::new(), |(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn cube_corners_and_edges() {
+        assert_eq!(nearest_256((0, 0, 0)), 16);
+        assert_eq!(nearest_256((255, 255, 255)), 231);
+        assert_eq!(nearest_256((255, 0, 0)), 196);
+        assert_eq!(nearest_256((0, 255, 0)), 46);
+        assert_eq!(nearest_256((0, 0, 255)), 21);
+        // 95 is the first step up from 0, anything past halfway there rounds up
+        assert_eq!(nearest_256((95, 0, 255)), 57);
+        assert_eq!(nearest_256((48, 0, 255)), 57);
+        assert_eq!(nearest_256((47, 0, 255)), 21);
+    }
+
+    #[test]
+    fn grays_use_the_ramp() {
+        assert_eq!(nearest_256((8, 8, 8)), 232);
+        assert_eq!(nearest_256((128, 128, 128)), 244);
+        assert_eq!(nearest_256((238, 238, 238)), 255);
+        // the cube's own grays win when they're exact
+        assert_eq!(nearest_256((95, 95, 95)), 59);
+        // a slight tint is still closer to a gray than to any cube color
+        assert_eq!(nearest_256((130, 128, 126)), 244);
+    }
+
+    #[test]
+    fn palette_round_trip() {
+        for i in 16..=255u8 {
+            assert_eq!(
+                indexed_rgb(nearest_256(indexed_rgb(i))),
+                indexed_rgb(i),
+                "{i}"
+            );
+        }
+    }
+
+    #[test]
+    fn down_to_16() {
+        let q = |c| quantize(c, ColorDepth::Ansi16);
+        assert_eq!(q(Color::Rgb(250, 10, 10)), Color::LightRed);
+        assert_eq!(q(Color::Rgb(190, 0, 0)), Color::Red);
+        assert_eq!(q(Color::Rgb(10, 10, 10)), Color::Black);
+        assert_eq!(q(Color::Rgb(130, 130, 130)), Color::DarkGray);
+        assert_eq!(q(Color::Indexed(196)), Color::LightRed);
+        assert_eq!(q(Color::Indexed(3)), Color::Yellow);
+        // named colors are already there
+        assert_eq!(q(Color::Cyan), Color::Cyan);
+    }
+
+    #[test]
+    fn through_each_depth() {
+        let rgb = Color::Rgb(255, 135, 0);
+        assert_eq!(quantize(rgb, ColorDepth::TrueColor), rgb);
+        assert_eq!(quantize(rgb, ColorDepth::Ansi256), Color::Indexed(208));
+        assert_eq!(
+            quantize(quantize(rgb, ColorDepth::Ansi256), ColorDepth::Ansi16),
+            Color::Yellow
+        );
+        assert_eq!(
+            quantize(Color::Indexed(208), ColorDepth::Ansi256),
+            Color::Indexed(208)
+        );
+    }
+
+    #[test]
+    fn no_color_drops_everything() {
+        for c in [Color::Rgb(1, 2, 3), Color::Indexed(100), Color::Red] {
+            assert_eq!(quantize(c, ColorDepth::None), Color::Reset);
+        }
+        let theme = crate::theme::Theme::default().with_depth(ColorDepth::None);
+        assert_eq!(theme.text, Color::Reset);
+        assert!(theme
+            .error()
+            .add_modifier
+            .contains(ratatui::style::Modifier::REVERSED));
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(
+            detect_from(None, env(&[("NO_COLOR", "1")])),
+            ColorDepth::None
+        );
+        // the config beats NO_COLOR
+        assert_eq!(
+            detect_from(Some(ColorDepth::Ansi256), env(&[("NO_COLOR", "1")])),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            detect_from(None, env(&[("COLORTERM", "truecolor"), ("TERM", "xterm")])),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            detect_from(None, env(&[("TERM", "xterm-256color")])),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            detect_from(None, env(&[("TERM", "screen")])),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            detect_from(None, env(&[("TERM", "dumb")])),
+            ColorDepth::None
+        );
+        assert_eq!(
+            detect_from(None, env(&[("TERM", "xterm")])),
+            ColorDepth::Ansi16
+        );
+    }
+}
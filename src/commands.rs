@@ -0,0 +1,215 @@
+use crate::action::{Action, Effect};
+use crate::app::{App, Page};
+use crate::chat_tui::{last_link, nth_link};
+use crate::emoji;
+use crate::help::Help;
+
+// the slash commands the client handles itself. adding one is adding an entry to COMMANDS,
+// /help and everything else that lists commands read it from there
+pub struct Command {
+    pub name: &'static str,
+    // what goes after the name, [optional] <required>
+    pub args: &'static str,
+    pub help: &'static str,
+    // gets everything after the name, trimmed. an Err shows up in the chat with the usage
+    run: fn(&mut App, &str) -> Result<Vec<Effect>, String>,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        if self.args.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.args)
+        }
+    }
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        args: "[search]",
+        help: "Shows the keys and commands, searched for [search]",
+        run: help,
+    },
+    Command {
+        name: "me",
+        args: "<action>",
+        help: "Says what you're doing, /me waves",
+        run: me,
+    },
+    Command {
+        name: "clear",
+        args: "",
+        help: "Clears the chat on this screen, nobody else's",
+        run: clear,
+    },
+    Command {
+        name: "open",
+        args: "[n]",
+        help: "Opens link [n] from the chat, the newest one without a number",
+        run: open,
+    },
+    Command {
+        name: "edit",
+        args: "",
+        help: "Writes the message in $VISUAL / $EDITOR",
+        run: edit,
+    },
+    Command {
+        name: "theme",
+        args: "[name]",
+        help: "Switches the theme, opens the picker without a name",
+        run: theme,
+    },
+    Command {
+        name: "emoji",
+        args: "[search]",
+        help: "Picks an emoji to put in the message",
+        run: emoji,
+    },
+    Command {
+        name: "glyphs",
+        args: "",
+        help: "Picks between nerd font, unicode and ascii icons",
+        run: glyphs,
+    },
+    Command {
+        name: "mouse",
+        args: "",
+        help: "Lets go of the mouse so the terminal can select text, again to take it back",
+        run: mouse,
+    },
+    Command {
+        name: "diag",
+        args: "",
+        help: "Shows what the client found out about the terminal",
+        run: diag,
+    },
+    Command {
+        name: "logout",
+        args: "",
+        help: "Hangs up and goes back to the login screen",
+        run: logout,
+    },
+    Command {
+        name: "quit",
+        args: "",
+        help: "Closes the client",
+        run: quit,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+// a leading // sends the rest with one slash, for when you really mean /something
+pub fn unescape(msg: &str) -> &str {
+    if msg.starts_with("//") {
+        &msg[1..]
+    } else {
+        msg
+    }
+}
+
+// runs `msg` if it's a command, None means it's a normal message to send
+pub fn run(app: &mut App, msg: &str) -> Option<Vec<Effect>> {
+    let line = msg.strip_prefix('/').filter(|l| !l.starts_with('/'))?;
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    // a typo stays in the composer to be fixed
+    let Some(command) = find(name) else {
+        app.notice(format!(
+            "Unknown command /{name}, /help lists them (start with // to send a /)"
+        ));
+        return Some(vec![]);
+    };
+    // cleared before running, some commands put something else in there
+    let typed = app.composer.text().to_string();
+    let cursor = app.composer.cursor();
+    app.composer.clear();
+    match (command.run)(app, args.trim()) {
+        Ok(effects) => Some(effects),
+        Err(e) => {
+            app.composer.restore(&typed, cursor);
+            app.notice(format!("{e}. Usage: {}", command.usage()));
+            Some(vec![])
+        }
+    }
+}
+
+fn help(app: &mut App, args: &str) -> Result<Vec<Effect>, String> {
+    app.help = Some(Help::new(args.trim_start_matches('/')));
+    Ok(vec![])
+}
+
+fn me(_app: &mut App, args: &str) -> Result<Vec<Effect>, String> {
+    if args.is_empty() {
+        return Err("What are you doing?".to_string());
+    }
+    Ok(vec![Effect::SendChat(format!("*{}*", emoji::expand(args)))])
+}
+
+fn clear(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    app.clear_chat();
+    Ok(vec![])
+}
+
+fn open(app: &mut App, args: &str) -> Result<Vec<Effect>, String> {
+    let url = if args.is_empty() {
+        last_link(&app.chat_messages).ok_or("No links in the chat yet")?
+    } else {
+        let n = args
+            .parse::<usize>()
+            .map_err(|_| format!("/open takes a link number, got {args}"))?;
+        nth_link(&app.chat_messages, n).ok_or_else(|| format!("No link [{n}]"))?
+    };
+    Ok(vec![Effect::OpenUrl(url)])
+}
+
+fn edit(_app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    Ok(vec![Effect::EditExternally(String::new())])
+}
+
+// /theme name switches and remembers it, plain /theme opens the picker
+fn theme(app: &mut App, args: &str) -> Result<Vec<Effect>, String> {
+    if args.is_empty() {
+        return Ok(crate::theme_picker::update(app, Action::OpenThemePicker));
+    }
+    app.apply_theme(args)?;
+    app.config.theme = Some(app.theme_name.clone());
+    Ok(app.save_config())
+}
+
+fn emoji(app: &mut App, args: &str) -> Result<Vec<Effect>, String> {
+    Ok(crate::emoji_picker::update(
+        app,
+        Action::OpenEmojiPicker(args.to_string()),
+    ))
+}
+
+fn glyphs(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    Ok(crate::glyph_picker::update(app, Action::OpenGlyphPicker))
+}
+
+fn mouse(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    Ok(crate::update::toggle_mouse(app))
+}
+
+fn diag(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    app.show_diagnostics = true;
+    Ok(vec![])
+}
+
+fn logout(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    app.token = None;
+    app.page = Page::Auth;
+    app.clear_chat();
+    crate::auth_tui::clear_credentials(app);
+    Ok(vec![Effect::Disconnect])
+}
+
+fn quit(app: &mut App, _args: &str) -> Result<Vec<Effect>, String> {
+    app.should_quit = true;
+    Ok(vec![])
+}
@@ -0,0 +1,239 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::ops::Range;
+
+use crate::app::App;
+use crate::commands::COMMANDS;
+use crate::emoji;
+use crate::text_layout::str_width;
+
+// how many candidates the popup shows at once
+const POPUP_ROWS: usize = 8;
+
+pub struct Candidate {
+    // what goes in the composer
+    pub text: String,
+    // what the popup shows, and a dimmer bit after it
+    pub label: String,
+    pub detail: String,
+}
+
+// something that knows how to finish a word. `before` is the composer text up to the word,
+// None means the word isn't one of ours and the next provider gets a look
+pub trait Provider {
+    fn complete(&self, app: &App, before: &str, word: &str) -> Option<Vec<Candidate>>;
+}
+
+// asked in order, the first one that answers wins
+const PROVIDERS: &[&dyn Provider] = &[&Commands, &Users, &Emoji];
+
+// Tab was pressed, the word before the cursor is being cycled through the candidates
+pub struct Completion {
+    pub candidates: Vec<Candidate>,
+    pub selected: usize,
+    // the bytes the current candidate takes up in the composer
+    range: Range<usize>,
+    // the word as typed, Esc puts it back
+    original: String,
+}
+
+// from the last whitespace to the cursor
+fn word_range(text: &str, cursor: usize) -> Range<usize> {
+    let start = text[..cursor].rfind(char::is_whitespace).map_or(0, |i| {
+        i + text[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    start..cursor
+}
+
+// first Tab, None if nothing matched. a single match goes straight in without a popup
+pub fn start(app: &mut App) -> Option<Completion> {
+    let text = app.composer.text().to_string();
+    let range = word_range(&text, app.composer.cursor());
+    let word = &text[range.clone()];
+    let before = &text[..range.start];
+    let candidates = PROVIDERS
+        .iter()
+        .find_map(|p| p.complete(app, before, word))
+        .filter(|c| !c.is_empty())?;
+    let original = word.to_string();
+    let mut completion = Completion {
+        candidates,
+        selected: 0,
+        range: range.clone(),
+        original,
+    };
+    completion.apply(app, 0);
+    (completion.candidates.len() > 1).then_some(completion)
+}
+
+impl Completion {
+    fn apply(&mut self, app: &mut App, index: usize) {
+        self.selected = index;
+        let text = &self.candidates[index].text;
+        app.composer.replace_range(self.range.clone(), text);
+        self.range = self.range.start..self.range.start + text.len();
+    }
+
+    // Tab and Shift+Tab
+    pub fn step(&mut self, app: &mut App, forward: bool) {
+        let len = self.candidates.len();
+        let index = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+        self.apply(app, index);
+    }
+
+    pub fn cancel(self, app: &mut App) {
+        app.composer.replace_range(self.range, &self.original);
+    }
+}
+
+struct Commands;
+
+impl Provider for Commands {
+    fn complete(&self, _app: &App, before: &str, word: &str) -> Option<Vec<Candidate>> {
+        let name = word.strip_prefix('/').filter(|_| before.is_empty())?;
+        Some(
+            COMMANDS
+                .iter()
+                .filter(|c| c.name.starts_with(name))
+                .map(|c| Candidate {
+                    text: format!("/{} ", c.name),
+                    label: c.usage(),
+                    detail: c.help.to_string(),
+                })
+                .collect(),
+        )
+    }
+}
+
+// @someone, from whoever talked most recently
+struct Users;
+
+impl Provider for Users {
+    fn complete(&self, app: &App, _before: &str, word: &str) -> Option<Vec<Candidate>> {
+        let name = word.strip_prefix('@')?.to_lowercase();
+        let mut users: Vec<&str> = Vec::new();
+        for msg in app.chat_messages.iter().rev().filter(|m| !m.system) {
+            if !users.contains(&msg.user.as_str()) {
+                users.push(&msg.user);
+            }
+        }
+        Some(
+            users
+                .into_iter()
+                .filter(|u| u.to_lowercase().starts_with(&name))
+                .map(|u| Candidate {
+                    text: format!("@{u} "),
+                    label: format!("@{u}"),
+                    detail: String::new(),
+                })
+                .collect(),
+        )
+    }
+}
+
+// :name turns into the emoji itself
+struct Emoji;
+
+impl Provider for Emoji {
+    fn complete(&self, _app: &App, _before: &str, word: &str) -> Option<Vec<Candidate>> {
+        let name = word
+            .strip_prefix(':')
+            .map(|n| n.trim_end_matches(':'))
+            .filter(|n| !n.is_empty())?;
+        Some(
+            emoji::search(name)
+                .into_iter()
+                .map(|(name, emoji)| Candidate {
+                    text: emoji.to_string(),
+                    label: format!("{emoji} :{name}:"),
+                    detail: String::new(),
+                })
+                .collect(),
+        )
+    }
+}
+
+// a little list sitting on top of the composer
+pub fn ui(f: &mut Frame, app: &App, composer: Rect) {
+    let Some(completion) = &app.completion else {
+        return;
+    };
+    let theme = &app.theme;
+    let rows = completion.candidates.len().min(POPUP_ROWS);
+    // keep the selected one in view
+    let first = completion.selected.saturating_sub(rows - 1);
+    let shown = &completion.candidates[first..first + rows];
+
+    let width = shown
+        .iter()
+        .map(|c| {
+            str_width(&c.label)
+                + if c.detail.is_empty() {
+                    0
+                } else {
+                    str_width(&c.detail) + 2
+                }
+        })
+        .max()
+        .unwrap_or(0) as u16
+        + 2;
+    let width = width.min(f.area().width);
+    let height = (rows as u16 + 2).min(composer.y);
+    if height < 3 {
+        return;
+    }
+    let x = (composer.x + 1).min(f.area().width.saturating_sub(width));
+    let area = Rect {
+        x,
+        y: composer.y - height,
+        width,
+        height,
+    };
+
+    let lines: Vec<Line> = shown
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let label = if first + i == completion.selected {
+                Span::styled(c.label.clone(), theme.focused(theme.button_focus))
+            } else {
+                Span::styled(c.label.clone(), Style::default().fg(theme.text))
+            };
+            let mut spans = vec![label];
+            if !c.detail.is_empty() {
+                spans.push(Span::styled(
+                    format!("  {}", c.detail),
+                    Style::default()
+                        .fg(theme.hint)
+                        .add_modifier(Modifier::ITALIC),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(format!(
+                    " {}/{} ",
+                    completion.selected + 1,
+                    completion.candidates.len()
+                )),
+        ),
+        area,
+    );
+}
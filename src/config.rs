@@ -0,0 +1,97 @@
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::colors::ColorDepth;
+use crate::glyphs::GlyphSet;
+
+// where our files live, everything that reads or writes one gets handed this. None when
+// there's no home to put them in
+#[derive(Clone, Default, Debug)]
+pub struct Dirs {
+    pub config: Option<PathBuf>,
+    pub data: Option<PathBuf>,
+}
+
+impl Dirs {
+    // ~/.config/reetui and ~/.local/share/reetui on linux
+    pub fn standard() -> Self {
+        let dirs = ProjectDirs::from("app", "reetui", "reetui");
+        Dirs {
+            config: dirs.as_ref().map(|d| d.config_dir().to_path_buf()),
+            data: dirs.as_ref().map(|d| d.data_dir().to_path_buf()),
+        }
+    }
+
+    pub fn config_file(&self, name: &str) -> Option<PathBuf> {
+        self.config.as_ref().map(|dir| dir.join(name))
+    }
+
+    pub fn data_file(&self, name: &str) -> Option<PathBuf> {
+        self.data.as_ref().map(|dir| dir.join(name))
+    }
+}
+
+// writes next to the file and renames it over, so a crash halfway leaves the old file
+// rather than a truncated one
+pub fn write_file(path: &Path, data: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Couldn't create {}: {e}", dir.display()))?;
+    }
+    let name = path.file_name().ok_or("Not a file")?.to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+    std::fs::write(&tmp, data)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            format!("Couldn't write {}: {e}", path.display())
+        })
+}
+
+// config.json, everything is optional so an old or half written file still loads
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    // a preset name or "custom", unset means the custom theme if there is one, dark otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    // "truecolor", "256", "16" or "none", unset means detect it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_depth: Option<ColorDepth>,
+    // "nerdfont", "unicode" or "ascii", unset means probe once and remember
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glyphs: Option<GlyphSet>,
+    // vim style normal/insert/visual modes in the composer
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub vim: bool,
+    // longest message the composer lets you send, in characters, unset means 2000
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_message_length: Option<usize>,
+}
+
+const DEFAULT_MAX_MESSAGE_LENGTH: usize = 2000;
+
+impl Config {
+    pub fn max_message_length(&self) -> usize {
+        self.max_message_length
+            .unwrap_or(DEFAULT_MAX_MESSAGE_LENGTH)
+    }
+
+    pub fn load(dirs: &Dirs) -> Result<Self, String> {
+        let Some(path) = dirs.config_file("config.json").filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+        serde_json::from_str(&data).map_err(|e| format!("Invalid config.json: {e}"))
+    }
+
+    pub fn save(&self, dirs: &Dirs) -> Result<(), String> {
+        let path = dirs
+            .config_file("config.json")
+            .ok_or("No config directory")?;
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_file(&path, &data)
+    }
+}
@@ -0,0 +1,156 @@
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::app::App;
+use crate::auth_tui::fixed_rect_in_center;
+use crate::background::Background;
+
+// F12 or /diag, everything we guessed about the terminal in one place, for bug reports
+fn rows(app: &App) -> Vec<(&'static str, String)> {
+    let bg = &app.background;
+    let background = match (bg.rgb, bg.background()) {
+        (Some((r, g, b)), Some(kind)) => format!(
+            "#{r:02x}{g:02x}{b:02x}, {} ({}ms)",
+            if kind == Background::Light {
+                "light"
+            } else {
+                "dark"
+            },
+            bg.took.as_millis()
+        ),
+        _ if bg.answered => "terminal doesn't support OSC 11".to_string(),
+        _ => format!("no answer after {}ms", bg.took.as_millis()),
+    };
+    let source = |set: bool| if set { "config" } else { "detected" };
+    let env = |name| std::env::var(name).unwrap_or_else(|_| "-".to_string());
+
+    let keymap = &app.keymap;
+    let keybindings = match &keymap.source {
+        Some(path) => format!("{} ({} problems)", path.display(), keymap.problems.len()),
+        None => "defaults".to_string(),
+    };
+
+    let mut rows = vec![
+        ("Background", background),
+        (
+            "Theme",
+            format!(
+                "{} ({})",
+                app.theme_name,
+                source(app.config.theme.is_some())
+            ),
+        ),
+        (
+            "Colors",
+            format!(
+                "{} ({})",
+                app.color_depth.name(),
+                source(app.config.color_depth.is_some())
+            ),
+        ),
+        (
+            "Glyphs",
+            format!(
+                "{} ({})",
+                app.glyph_set.name(),
+                source(app.config.glyphs.is_some())
+            ),
+        ),
+        (
+            "Hyperlinks",
+            if app.hyperlinks_supported {
+                "osc 8"
+            } else {
+                "/open"
+            }
+            .to_string(),
+        ),
+        (
+            "Keyboard",
+            format!(
+                "{}, newline is {}",
+                if app.keyboard_enhanced {
+                    "kitty protocol"
+                } else {
+                    "legacy"
+                },
+                app.newline_key().unwrap_or_else(|| "unbound".to_string())
+            ),
+        ),
+        (
+            "Mouse",
+            if app.mouse_captured {
+                "captured"
+            } else {
+                "released, the terminal selects text"
+            }
+            .to_string(),
+        ),
+        ("TERM", env("TERM")),
+        ("COLORTERM", env("COLORTERM")),
+        ("TERM_PROGRAM", env("TERM_PROGRAM")),
+        (
+            "Config",
+            app.dirs
+                .config
+                .as_ref()
+                .map_or("-".to_string(), |d| d.display().to_string()),
+        ),
+        ("Keybindings", keybindings),
+    ];
+    // whatever's wrong with keybindings.toml, one per line under it
+    rows.extend(keymap.problems.iter().map(|p| ("", p.clone())));
+    rows
+}
+
+pub fn ui(f: &mut Frame, app: &App) {
+    if !app.show_diagnostics {
+        return;
+    }
+    let theme = &app.theme;
+    let rows = rows(app);
+    let width = rows
+        .iter()
+        .map(|(_, v)| v.len() as u16 + 17)
+        .max()
+        .unwrap_or(0)
+        .max(40);
+    let area = fixed_rect_in_center(f.area(), width, rows.len() as u16 + 4);
+
+    let mut lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(key, value)| {
+            Line::from(vec![
+                Span::styled(format!("{key:<14}"), Style::default().fg(theme.muted)),
+                Span::styled(value, Style::default().fg(theme.text)),
+            ])
+        })
+        .collect();
+    lines.push(Line::raw(""));
+    lines.push(Line::from(Span::styled(
+        "any key to close",
+        Style::default()
+            .fg(theme.hint)
+            .add_modifier(Modifier::ITALIC),
+    )));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Diagnostics ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::config::{self, Dirs};
+
+// the unsent message, kept in data_dir/drafts/<server>.json so a crash or a restart doesn't
+// eat it. the server has the one room, so it's one per server
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct Draft {
+    pub text: String,
+    pub cursor: usize,
+}
+
+fn drafts_path(dirs: &Dirs, server: &str) -> Option<PathBuf> {
+    dirs.data_file("drafts")
+        .map(|dir| dir.join(format!("{server}.json")))
+}
+
+// a missing file is just no draft
+pub fn load(dirs: &Dirs, server: &str) -> Result<Draft, String> {
+    let Some(path) = drafts_path(dirs, server).filter(|p| p.exists()) else {
+        return Ok(Draft::default());
+    };
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+    serde_json::from_str(&data).map_err(|e| format!("Invalid draft: {e}"))
+}
+
+pub fn save(dirs: &Dirs, server: &str, draft: &Draft) -> Result<(), String> {
+    let path = drafts_path(dirs, server).ok_or("No data directory")?;
+    let data = serde_json::to_string(draft).map_err(|e| e.to_string())?;
+    config::write_file(&path, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(name: &str) -> Dirs {
+        let dir = std::env::temp_dir().join(format!("reetui-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Dirs {
+            config: None,
+            data: Some(dir),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let dirs = dirs("drafts-round-trip");
+        assert_eq!(load(&dirs, "example.com"), Ok(Draft::default()));
+
+        let draft = Draft {
+            text: "half a thought\nand é".to_string(),
+            cursor: 7,
+        };
+        save(&dirs, "example.com", &draft).unwrap();
+        assert_eq!(load(&dirs, "example.com"), Ok(draft.clone()));
+        // each server has its own
+        assert_eq!(load(&dirs, "other.org"), Ok(Draft::default()));
+
+        // saving over it replaces it and leaves no temp file behind
+        save(&dirs, "example.com", &Draft::default()).unwrap();
+        assert_eq!(load(&dirs, "example.com"), Ok(Draft::default()));
+        let files = std::fs::read_dir(dirs.data_file("drafts").unwrap())
+            .unwrap()
+            .count();
+        assert_eq!(files, 1);
+
+        let _ = std::fs::remove_dir_all(dirs.data.unwrap());
+    }
+
+    #[test]
+    fn nowhere_to_save() {
+        assert!(save(&Dirs::default(), "example.com", &Draft::default()).is_err());
+        assert_eq!(load(&Dirs::default(), "example.com"), Ok(Draft::default()));
+    }
+
+    #[test]
+    fn broken_file() {
+        let dirs = dirs("drafts-broken");
+        let path = drafts_path(&dirs, "example.com").unwrap();
+        config::write_file(&path, "{\"text\": ").unwrap();
+        assert!(load(&dirs, "example.com").is_err());
+        let _ = std::fs::remove_dir_all(dirs.data.unwrap());
+    }
+}
@@ -0,0 +1,613 @@
+use std::ops::Range;
+
+use crate::text_layout::{next_grapheme, prev_grapheme};
+
+// how many kills Alt+Y can cycle back through
+const KILL_RING_SIZE: usize = 16;
+
+// undo keeps at most this many steps and roughly this much text, oldest goes first
+const UNDO_STEPS: usize = 200;
+const UNDO_BYTES: usize = 256 * 1024;
+
+// a text buffer with a cursor and the readline style editing everyone's fingers expect, the
+// composer and the login inputs both sit on one of these. the cursor is a byte offset that
+// always lands on a grapheme boundary
+#[derive(Default)]
+pub struct Editor {
+    text: String,
+    cursor: usize,
+    kill_ring: Vec<String>,
+    last: LastOp,
+    undo: Vec<Group>,
+    redo: Vec<Group>,
+    // what everything in undo adds up to, kept as it changes
+    undo_bytes: usize,
+    // the newest undo group can still take more typing, moving the cursor closes it
+    group_open: bool,
+}
+
+// what kind of change, so typing a word or holding backspace undoes in one go
+#[derive(Clone, Copy, PartialEq)]
+enum EditKind {
+    Typing,
+    Deleting,
+    Other,
+}
+
+struct Edit {
+    at: usize,
+    removed: String,
+    inserted: String,
+}
+
+// one undo step
+struct Group {
+    kind: EditKind,
+    edits: Vec<Edit>,
+    cursor_before: usize,
+    cursor_after: usize,
+    bytes: usize,
+}
+
+impl Edit {
+    fn bytes(&self) -> usize {
+        self.removed.len() + self.inserted.len()
+    }
+}
+
+// consecutive kills pile up into one ring entry, and Alt+Y only works right after a yank
+#[derive(Default, Clone, PartialEq)]
+enum LastOp {
+    #[default]
+    Other,
+    Kill,
+    Yank {
+        range: Range<usize>,
+        index: usize,
+    },
+}
+
+fn is_word(g: &str) -> bool {
+    g.chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl Editor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.len());
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    // swaps the whole text in as one undo step, cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.replace(0..self.text.len(), text, EditKind::Other);
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    // a fresh start after sending, nothing to undo back into
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.last = LastOp::Other;
+        self.undo.clear();
+        self.redo.clear();
+        self.undo_bytes = 0;
+        self.group_open = false;
+    }
+
+    // a different buffer altogether (a saved draft), nothing to undo back into
+    pub fn restore(&mut self, text: &str, cursor: usize) {
+        self.clear();
+        self.text = text.to_string();
+        self.cursor = if text.is_char_boundary(cursor.min(text.len())) {
+            cursor.min(text.len())
+        } else {
+            text.len()
+        };
+    }
+
+    // every change goes through here, the cursor ends up after the new text
+    fn replace(&mut self, range: Range<usize>, with: &str, kind: EditKind) {
+        if range.is_empty() && with.is_empty() {
+            return;
+        }
+        let edit = Edit {
+            at: range.start,
+            removed: self.text[range.clone()].to_string(),
+            inserted: with.to_string(),
+        };
+        let cursor_before = self.cursor;
+        self.text.replace_range(range.clone(), with);
+        self.cursor = range.start + with.len();
+        self.record(edit, kind, cursor_before);
+    }
+
+    fn record(&mut self, edit: Edit, kind: EditKind, cursor_before: usize) {
+        self.redo.clear();
+        // a word and the space after it go together, the next word starts a new step
+        let starts_word = kind == EditKind::Typing
+            && !edit.inserted.chars().all(char::is_whitespace)
+            && self.undo.last().is_some_and(|g| {
+                g.edits
+                    .last()
+                    .is_some_and(|e| e.inserted.ends_with(char::is_whitespace))
+            });
+        let bytes = edit.bytes();
+        self.undo_bytes += bytes;
+        match self.undo.last_mut() {
+            // a step that got too big on its own closes, so the cap can drop the older part
+            Some(group)
+                if self.group_open
+                    && kind != EditKind::Other
+                    && group.kind == kind
+                    && group.cursor_after == cursor_before
+                    && !starts_word
+                    && group.bytes + bytes <= UNDO_BYTES =>
+            {
+                group.edits.push(edit);
+                group.cursor_after = self.cursor;
+                group.bytes += bytes;
+            }
+            _ => self.undo.push(Group {
+                kind,
+                edits: vec![edit],
+                cursor_before,
+                cursor_after: self.cursor,
+                bytes,
+            }),
+        }
+        while self.undo.len() > UNDO_STEPS || (self.undo_bytes > UNDO_BYTES && self.undo.len() > 1)
+        {
+            self.undo_bytes -= self.undo.remove(0).bytes;
+        }
+        self.group_open = kind != EditKind::Other;
+    }
+
+    pub fn undo(&mut self) {
+        let Some(group) = self.undo.pop() else {
+            return;
+        };
+        self.undo_bytes -= group.bytes;
+        for edit in group.edits.iter().rev() {
+            self.text
+                .replace_range(edit.at..edit.at + edit.inserted.len(), &edit.removed);
+        }
+        self.cursor = group.cursor_before;
+        self.redo.push(group);
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    pub fn redo(&mut self) {
+        let Some(group) = self.redo.pop() else {
+            return;
+        };
+        for edit in &group.edits {
+            self.text
+                .replace_range(edit.at..edit.at + edit.removed.len(), &edit.inserted);
+        }
+        self.cursor = group.cursor_after;
+        self.undo_bytes += group.bytes;
+        self.undo.push(group);
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    // one undo step that swaps out any range, for callers with their own idea of motions
+    pub fn replace_range(&mut self, range: Range<usize>, with: &str) {
+        self.replace(range, with, EditKind::Other);
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.replace(
+            self.cursor..self.cursor,
+            c.encode_utf8(&mut [0; 4]),
+            EditKind::Typing,
+        );
+        self.last = LastOp::Other;
+    }
+
+    // a paste, one undo step however big it is
+    pub fn insert_str(&mut self, s: &str) {
+        self.replace(self.cursor..self.cursor, s, EditKind::Other);
+        self.last = LastOp::Other;
+        self.group_open = false;
+    }
+
+    pub fn backspace(&mut self) {
+        let prev = prev_grapheme(&self.text, self.cursor);
+        self.replace(prev..self.cursor, "", EditKind::Deleting);
+        self.last = LastOp::Other;
+    }
+
+    pub fn delete(&mut self) {
+        let next = next_grapheme(&self.text, self.cursor);
+        let cursor = self.cursor;
+        self.replace(cursor..next, "", EditKind::Deleting);
+        self.last = LastOp::Other;
+    }
+
+    pub fn left(&mut self) {
+        self.set_cursor(prev_grapheme(&self.text, self.cursor));
+    }
+
+    pub fn right(&mut self) {
+        self.set_cursor(next_grapheme(&self.text, self.cursor));
+    }
+
+    // start of the word before the cursor, skipping whatever isn't a word on the way
+    fn word_start(&self) -> usize {
+        let mut pos = self.cursor;
+        let mut seen_word = false;
+        while pos > 0 {
+            let prev = prev_grapheme(&self.text, pos);
+            let word = is_word(&self.text[prev..pos]);
+            if seen_word && !word {
+                break;
+            }
+            seen_word |= word;
+            pos = prev;
+        }
+        pos
+    }
+
+    fn word_end(&self) -> usize {
+        let mut pos = self.cursor;
+        let mut seen_word = false;
+        while pos < self.text.len() {
+            let next = next_grapheme(&self.text, pos);
+            let word = is_word(&self.text[pos..next]);
+            if seen_word && !word {
+                break;
+            }
+            seen_word |= word;
+            pos = next;
+        }
+        pos
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.cursor + i)
+    }
+
+    pub fn word_left(&mut self) {
+        self.set_cursor(self.word_start());
+    }
+
+    pub fn word_right(&mut self) {
+        self.set_cursor(self.word_end());
+    }
+
+    // cuts text next to the cursor into the kill ring, back to back kills grow the same entry
+    fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let killed = self.text[range.clone()].to_string();
+        let backwards = range.end == self.cursor;
+        match self.kill_ring.last_mut() {
+            Some(last) if self.last == LastOp::Kill => {
+                if backwards {
+                    last.insert_str(0, &killed);
+                } else {
+                    last.push_str(&killed);
+                }
+            }
+            _ => {
+                self.kill_ring.push(killed);
+                if self.kill_ring.len() > KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+            }
+        }
+        self.replace(range, "", EditKind::Other);
+        self.last = LastOp::Kill;
+    }
+
+    pub fn kill_word_back(&mut self) {
+        self.kill(self.word_start()..self.cursor);
+    }
+
+    pub fn kill_word_forward(&mut self) {
+        self.kill(self.cursor..self.word_end());
+    }
+
+    // at the edge of a line these take the newline, so repeating them keeps going
+    pub fn kill_to_start(&mut self) {
+        let start = match self.line_start() {
+            start if start == self.cursor => prev_grapheme(&self.text, self.cursor),
+            start => start,
+        };
+        self.kill(start..self.cursor);
+    }
+
+    pub fn kill_to_end(&mut self) {
+        let end = match self.line_end() {
+            end if end == self.cursor => next_grapheme(&self.text, self.cursor),
+            end => end,
+        };
+        self.kill(self.cursor..end);
+    }
+
+    // puts text in the kill ring without cutting anything, Ctrl+Y brings it back
+    pub fn copy(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.kill_ring.push(text);
+        if self.kill_ring.len() > KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.last = LastOp::Other;
+    }
+
+    pub fn last_kill(&self) -> Option<&str> {
+        self.kill_ring.last().map(String::as_str)
+    }
+
+    pub fn yank(&mut self) {
+        let Some(text) = self.kill_ring.last().cloned() else {
+            return;
+        };
+        let start = self.cursor;
+        self.replace(start..start, &text, EditKind::Other);
+        self.last = LastOp::Yank {
+            range: start..self.cursor,
+            index: self.kill_ring.len() - 1,
+        };
+    }
+
+    // swaps what the last yank put in for the kill before it
+    pub fn yank_pop(&mut self) {
+        let LastOp::Yank { range, index } = self.last.clone() else {
+            return;
+        };
+        let index = (index + self.kill_ring.len() - 1) % self.kill_ring.len();
+        let text = self.kill_ring[index].clone();
+        self.replace(range.clone(), &text, EditKind::Other);
+        self.last = LastOp::Yank {
+            range: range.start..self.cursor,
+            index,
+        };
+    }
+
+    // swaps the two graphemes around the cursor, at the end of a line the last two. never
+    // across a newline
+    pub fn transpose(&mut self) {
+        let line_start = self.line_start();
+        let mut pos = self.cursor;
+        if pos == self.line_end() {
+            pos = prev_grapheme(&self.text, pos).max(line_start);
+        }
+        let start = prev_grapheme(&self.text, pos);
+        let end = next_grapheme(&self.text, pos);
+        if pos == line_start || pos == end {
+            return;
+        }
+        let swapped = format!("{}{}", &self.text[pos..end], &self.text[start..pos]);
+        self.replace(start..end, &swapped, EditKind::Other);
+        self.last = LastOp::Other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> Editor {
+        let mut editor = Editor::default();
+        for c in text.chars() {
+            editor.insert_char(c);
+        }
+        editor
+    }
+
+    #[test]
+    fn typing_undoes_a_word_at_a_time() {
+        let mut editor = typed("hello there");
+        editor.undo();
+        assert_eq!(editor.text(), "hello ");
+        editor.undo();
+        assert_eq!(editor.text(), "");
+        editor.redo();
+        editor.redo();
+        assert_eq!(editor.text(), "hello there");
+        assert_eq!(editor.cursor(), editor.text().len());
+    }
+
+    #[test]
+    fn byte_total_follows_undo_and_redo() {
+        let mut editor = typed("ab cd");
+        assert_eq!(editor.undo_bytes, 5);
+        editor.undo();
+        assert_eq!(editor.undo_bytes, 3);
+        editor.redo();
+        assert_eq!(editor.undo_bytes, 5);
+        editor.clear();
+        assert_eq!(editor.undo_bytes, 0);
+    }
+
+    #[test]
+    fn one_long_word_stays_under_the_cap() {
+        // no spaces, so without the cap it'd all pile into a single step
+        let mut editor = Editor::default();
+        for _ in 0..UNDO_BYTES * 2 {
+            editor.insert_char('a');
+        }
+        let sum: usize = editor.undo.iter().map(|g| g.bytes).sum();
+        assert_eq!(editor.undo_bytes, sum);
+        assert!(editor.undo_bytes <= UNDO_BYTES);
+        assert!(editor.undo.iter().all(|g| g.bytes <= UNDO_BYTES));
+        // the newest typing is still undoable
+        editor.undo();
+        assert!(editor.text().len() < UNDO_BYTES * 2);
+    }
+
+    #[test]
+    fn steps_are_capped() {
+        let mut editor = Editor::default();
+        for i in 0..UNDO_STEPS + 10 {
+            editor.set_cursor(i);
+            editor.insert_char('x');
+        }
+        assert_eq!(editor.undo.len(), UNDO_STEPS);
+        assert_eq!(editor.undo_bytes, UNDO_STEPS);
+    }
+
+    fn at(text: &str, cursor: usize) -> Editor {
+        let mut editor = Editor::default();
+        editor.set_text(text);
+        editor.set_cursor(cursor);
+        editor
+    }
+
+    #[test]
+    fn word_motions_skip_punctuation() {
+        let mut editor = at("foo.bar  baz!", 13);
+        let mut stops = Vec::new();
+        for _ in 0..4 {
+            editor.word_left();
+            stops.push(editor.cursor());
+        }
+        assert_eq!(stops, [9, 4, 0, 0]);
+        stops.clear();
+        for _ in 0..4 {
+            editor.word_right();
+            stops.push(editor.cursor());
+        }
+        assert_eq!(stops, [3, 7, 12, 13]);
+    }
+
+    #[test]
+    fn word_motions_keep_graphemes_whole() {
+        // an accent made of two chars, and a thumbs up with a skin tone
+        let text = "he\u{301}llo 👍🏽 wo_rld";
+        let mut editor = at(text, text.len());
+        editor.word_left();
+        assert_eq!(&text[editor.cursor()..], "wo_rld");
+        editor.word_left();
+        assert_eq!(editor.cursor(), 0);
+        editor.word_right();
+        assert_eq!(&text[..editor.cursor()], "he\u{301}llo");
+        editor.word_right();
+        assert_eq!(editor.cursor(), text.len());
+        let mut editor = at(text, text.len());
+        editor.kill_word_back();
+        editor.kill_word_back();
+        assert_eq!(editor.text(), "");
+        assert_eq!(editor.last_kill(), Some(text));
+    }
+
+    #[test]
+    fn kills_in_a_row_pile_up() {
+        let mut editor = at("one two three", 13);
+        editor.kill_word_back();
+        editor.kill_word_back();
+        assert_eq!(editor.text(), "one ");
+        assert_eq!(editor.last_kill(), Some("two three"));
+
+        // forwards they go on the end
+        let mut editor = at("one two three", 0);
+        editor.kill_word_forward();
+        editor.kill_word_forward();
+        assert_eq!(editor.text(), " three");
+        assert_eq!(editor.last_kill(), Some("one two"));
+
+        // anything in between starts a new entry
+        let mut editor = at("one two three", 13);
+        editor.kill_word_back();
+        editor.left();
+        editor.kill_word_back();
+        assert_eq!(editor.last_kill(), Some("two"));
+        assert_eq!(editor.kill_ring.len(), 2);
+    }
+
+    #[test]
+    fn kill_lines_and_yank_back() {
+        let mut editor = at("ab\ncd", 4);
+        editor.kill_to_start();
+        assert_eq!(editor.text(), "ab\nd");
+        // at the start of the line the newline goes next, into the same kill
+        editor.kill_to_start();
+        assert_eq!(editor.text(), "abd");
+        assert_eq!(editor.last_kill(), Some("\nc"));
+        editor.kill_to_end();
+        assert_eq!(editor.text(), "ab");
+        // all three were one run of kills, so they come back together
+        editor.yank();
+        assert_eq!(editor.text(), "ab\ncd");
+    }
+
+    #[test]
+    fn yank_pop_cycles_the_ring() {
+        let mut editor = Editor::default();
+        for word in ["one", "two", "three"] {
+            editor.copy(word.to_string());
+        }
+        editor.yank();
+        assert_eq!(editor.text(), "three");
+        editor.yank_pop();
+        assert_eq!(editor.text(), "two");
+        editor.yank_pop();
+        assert_eq!(editor.text(), "one");
+        // and round again
+        editor.yank_pop();
+        assert_eq!(editor.text(), "three");
+        assert_eq!(editor.cursor(), 5);
+
+        // only straight after a yank
+        editor.insert_char('!');
+        editor.yank_pop();
+        assert_eq!(editor.text(), "three!");
+    }
+
+    #[test]
+    fn kill_ring_is_capped() {
+        let mut editor = Editor::default();
+        for i in 0..KILL_RING_SIZE + 5 {
+            editor.copy(i.to_string());
+        }
+        assert_eq!(editor.kill_ring.len(), KILL_RING_SIZE);
+        assert_eq!(editor.kill_ring[0], "5");
+    }
+
+    #[test]
+    fn transpose_in_the_middle_and_at_the_edges() {
+        let swapped = |text: &str, cursor: usize| {
+            let mut editor = at(text, cursor);
+            editor.transpose();
+            (editor.text().to_string(), editor.cursor())
+        };
+        assert_eq!(swapped("abc", 1), ("bac".to_string(), 2));
+        // at the end the last two swap
+        assert_eq!(swapped("abc", 3), ("acb".to_string(), 3));
+        // at the start there's nothing before
+        assert_eq!(swapped("abc", 0), ("abc".to_string(), 0));
+        assert_eq!(swapped("a", 1), ("a".to_string(), 1));
+        // the end of a line is the end of the line, not the newline
+        assert_eq!(swapped("ab\ncd", 2), ("ba\ncd".to_string(), 2));
+        assert_eq!(swapped("ab\ncd", 3), ("ab\ncd".to_string(), 3));
+        assert_eq!(swapped("ab\ncd", 4), ("ab\ndc".to_string(), 5));
+        // graphemes move as one
+        assert_eq!(swapped("xe\u{301}", 1), ("e\u{301}x".to_string(), 4));
+    }
+}
@@ -0,0 +1,254 @@
+use std::ops::Range;
+
+use crate::chat_tui::find_urls;
+use crate::config::{self, Dirs};
+
+// how many the picker's Recent row remembers
+const MAX_RECENT: usize = 24;
+
+// :shortcode: names for the emoji people actually use, github/slack spelling, grouped the way
+// the picker shows them
+pub const CATEGORIES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Smileys",
+        &[
+            ("smile", "😄"),
+            ("grin", "😁"),
+            ("joy", "😂"),
+            ("rofl", "🤣"),
+            ("sweat_smile", "😅"),
+            ("wink", "😉"),
+            ("blush", "😊"),
+            ("slightly_smiling_face", "🙂"),
+            ("upside_down_face", "🙃"),
+            ("heart_eyes", "😍"),
+            ("kissing_heart", "😘"),
+            ("yum", "😋"),
+            ("stuck_out_tongue", "😛"),
+            ("thinking", "🤔"),
+            ("neutral_face", "😐"),
+            ("expressionless", "😑"),
+            ("unamused", "😒"),
+            ("roll_eyes", "🙄"),
+            ("grimacing", "😬"),
+            ("relieved", "😌"),
+            ("pensive", "😔"),
+            ("sleepy", "😪"),
+            ("sleeping", "😴"),
+            ("sunglasses", "😎"),
+            ("nerd_face", "🤓"),
+            ("confused", "😕"),
+            ("worried", "😟"),
+            ("open_mouth", "😮"),
+            ("flushed", "😳"),
+            ("pleading_face", "🥺"),
+            ("cry", "😢"),
+            ("sob", "😭"),
+            ("scream", "😱"),
+            ("angry", "😠"),
+            ("rage", "😡"),
+            ("skull", "💀"),
+            ("clown_face", "🤡"),
+            ("ghost", "👻"),
+            ("robot", "🤖"),
+        ],
+    ),
+    (
+        "People",
+        &[
+            ("thumbsup", "👍"),
+            ("thumbsdown", "👎"),
+            ("ok_hand", "👌"),
+            ("wave", "👋"),
+            ("clap", "👏"),
+            ("pray", "🙏"),
+            ("muscle", "💪"),
+            ("raised_hands", "🙌"),
+            ("eyes", "👀"),
+            ("point_up", "☝️"),
+            ("v", "✌️"),
+            ("handshake", "🤝"),
+            ("shrug", "🤷"),
+            ("facepalm", "🤦"),
+        ],
+    ),
+    (
+        "Nature",
+        &[
+            ("cat", "🐱"),
+            ("dog", "🐶"),
+            ("crab", "🦀"),
+            ("snake", "🐍"),
+            ("bug", "🐛"),
+            ("fire", "🔥"),
+            ("star", "⭐"),
+            ("zap", "⚡"),
+            ("sparkles", "✨"),
+            ("fox_face", "🦊"),
+            ("penguin", "🐧"),
+            ("unicorn", "🦄"),
+            ("sunflower", "🌻"),
+            ("rainbow", "🌈"),
+            ("snowflake", "❄️"),
+        ],
+    ),
+    (
+        "Food",
+        &[
+            ("coffee", "☕"),
+            ("pizza", "🍕"),
+            ("beer", "🍺"),
+            ("apple", "🍎"),
+            ("taco", "🌮"),
+            ("cake", "🍰"),
+            ("cookie", "🍪"),
+            ("tea", "🍵"),
+        ],
+    ),
+    (
+        "Objects",
+        &[
+            ("tada", "🎉"),
+            ("rocket", "🚀"),
+            ("computer", "💻"),
+            ("gift", "🎁"),
+            ("bulb", "💡"),
+            ("lock", "🔒"),
+            ("key", "🔑"),
+            ("hammer", "🔨"),
+            ("package", "📦"),
+            ("memo", "📝"),
+        ],
+    ),
+    (
+        "Symbols",
+        &[
+            ("heart", "❤️"),
+            ("broken_heart", "💔"),
+            ("100", "💯"),
+            ("warning", "⚠️"),
+            ("x", "❌"),
+            ("white_check_mark", "✅"),
+            ("question", "❓"),
+            ("heavy_plus_sign", "➕"),
+            ("arrow_right", "➡️"),
+            ("recycle", "♻️"),
+        ],
+    ),
+];
+
+pub fn all() -> impl Iterator<Item = (&'static str, &'static str)> {
+    CATEGORIES
+        .iter()
+        .flat_map(|(_, emoji)| emoji.iter().copied())
+}
+
+pub fn get(name: &str) -> Option<&'static str> {
+    all().find(|(n, _)| *n == name).map(|(_, e)| e)
+}
+
+// names starting with `query` first, then ones with it anywhere
+pub fn search(query: &str) -> Vec<(&'static str, &'static str)> {
+    let query = query.to_lowercase();
+    let mut found: Vec<_> = all().filter(|(n, _)| n.starts_with(&query)).collect();
+    found.extend(all().filter(|(n, _)| !n.starts_with(&query) && n.contains(&query)));
+    found
+}
+
+// every :name: we know in `text`, skipping `code` and links (a url can have :colons: in it),
+// so expanding and the composer preview agree
+pub fn shortcodes(text: &str) -> Vec<(Range<usize>, &'static str)> {
+    let mut found = Vec::new();
+    let mut urls = find_urls(text).into_iter().peekable();
+    let mut in_code = false;
+    let mut i = 0;
+    while i < text.len() {
+        while urls.next_if(|url| url.end <= i).is_some() {}
+        if let Some(url) = urls.peek().filter(|url| url.start <= i && !in_code) {
+            i = url.end;
+            continue;
+        }
+        let c = text[i..].chars().next().unwrap_or(' ');
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == ':' && !in_code {
+            let rest = &text[i + 1..];
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-'))
+                .unwrap_or(rest.len());
+            if rest[len..].starts_with(':') {
+                if let Some(emoji) = get(&rest[..len]) {
+                    let end = i + len + 2;
+                    found.push((i..end, emoji));
+                    i = end;
+                    continue;
+                }
+            }
+        }
+        i += c.len_utf8();
+    }
+    found
+}
+
+// :tada: -> 🎉 before sending, unknown names stay as they are
+pub fn expand(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, emoji) in shortcodes(text) {
+        out.push_str(&text[last..range.start]);
+        out.push_str(emoji);
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+// names of the emoji used lately, newest first
+pub fn load_recent(dirs: &Dirs) -> Result<Vec<String>, String> {
+    let Some(path) = dirs.data_file("emoji.json").filter(|p| p.exists()) else {
+        return Ok(Vec::new());
+    };
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+    serde_json::from_str(&data).map_err(|e| format!("Invalid emoji.json: {e}"))
+}
+
+pub fn save_recent(dirs: &Dirs, recent: &[String]) -> Result<(), String> {
+    let path = dirs.data_file("emoji.json").ok_or("No data directory")?;
+    let data = serde_json::to_string(recent).map_err(|e| e.to_string())?;
+    config::write_file(&path, &data)
+}
+
+pub fn remember(recent: &mut Vec<String>, name: &str) {
+    recent.retain(|n| n != name);
+    recent.insert(0, name.to_string());
+    recent.truncate(MAX_RECENT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_known_names() {
+        assert_eq!(expand("hi :wave: :nope:"), "hi 👋 :nope:");
+        assert_eq!(expand(":tada::tada:"), "🎉🎉");
+    }
+
+    #[test]
+    fn leaves_code_alone() {
+        assert_eq!(expand("`:wave:` :wave:"), "`:wave:` 👋");
+    }
+
+    #[test]
+    fn leaves_links_alone() {
+        assert_eq!(
+            expand("https://a.com/:smile:/x :smile:"),
+            "https://a.com/:smile:/x 😄"
+        );
+        assert_eq!(
+            expand(":wave: www.a.com?q=:tada: :tada:"),
+            "👋 www.a.com?q=:tada: 🎉"
+        );
+    }
+}
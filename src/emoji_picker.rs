@@ -0,0 +1,190 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::action::{Action, Effect};
+use crate::app::App;
+use crate::auth_tui::{carousel, fixed_rect_in_center};
+use crate::emoji::{self, CATEGORIES};
+
+// Alt+E or /emoji. a carousel like the register icon picker, with a row of categories above
+// it (Recent first) and typing to search all of them
+pub struct EmojiPicker {
+    pub query: String,
+    // 0 is Recent, the rest are emoji::CATEGORIES shifted by one
+    pub category: usize,
+    pub selected: usize,
+}
+
+impl EmojiPicker {
+    // what the carousel is showing, (name, emoji)
+    fn items(&self, recent: &[String]) -> Vec<(&'static str, &'static str)> {
+        if !self.query.is_empty() {
+            return emoji::search(&self.query);
+        }
+        match self.category {
+            0 => recent
+                .iter()
+                .filter_map(|name| emoji::all().find(|(n, _)| n == name))
+                .collect(),
+            i => CATEGORIES[i - 1].1.to_vec(),
+        }
+    }
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    let Event::Key(KeyEvent { code, .. }) = evt else {
+        return None;
+    };
+    let action = match code {
+        KeyCode::Left => Action::PickerPrev,
+        KeyCode::Right => Action::PickerNext,
+        KeyCode::Tab | KeyCode::Down => Action::PickerCategory(true),
+        KeyCode::BackTab | KeyCode::Up => Action::PickerCategory(false),
+        KeyCode::Enter => Action::PickerConfirm,
+        KeyCode::Esc => Action::PickerCancel,
+        KeyCode::Backspace => Action::PickerErase,
+        KeyCode::Char(c) => Action::PickerType(c),
+        _ => return None,
+    };
+    Some(action)
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    if let Action::OpenEmojiPicker(query) = action {
+        app.emoji_picker = Some(EmojiPicker {
+            query,
+            category: if app.recent_emoji.is_empty() { 1 } else { 0 },
+            selected: 0,
+        });
+        return vec![];
+    }
+    let Some(picker) = &mut app.emoji_picker else {
+        return vec![];
+    };
+    let len = picker.items(&app.recent_emoji).len().max(1);
+    let categories = CATEGORIES.len() + 1;
+    match action {
+        Action::PickerPrev => picker.selected = (picker.selected + len - 1) % len,
+        Action::PickerNext => picker.selected = (picker.selected + 1) % len,
+        Action::PickerCategory(forward) => {
+            picker.category = if forward {
+                (picker.category + 1) % categories
+            } else {
+                (picker.category + categories - 1) % categories
+            };
+            picker.query.clear();
+            picker.selected = 0;
+        }
+        Action::PickerType(c) => {
+            picker.query.push(c);
+            picker.selected = 0;
+        }
+        Action::PickerErase => {
+            picker.query.pop();
+            picker.selected = 0;
+        }
+        Action::PickerConfirm => {
+            let picked = picker
+                .items(&app.recent_emoji)
+                .get(picker.selected)
+                .copied();
+            app.emoji_picker = None;
+            if let Some((name, emoji)) = picked {
+                app.composer.insert_str(emoji);
+                emoji::remember(&mut app.recent_emoji, name);
+                return vec![Effect::SaveRecentEmoji(app.recent_emoji.clone())];
+            }
+        }
+        Action::PickerCancel => app.emoji_picker = None,
+        _ => {}
+    }
+    vec![]
+}
+
+pub fn ui(f: &mut Frame, app: &App) {
+    let Some(picker) = &app.emoji_picker else {
+        return;
+    };
+    let theme = &app.theme;
+    let items = picker.items(&app.recent_emoji);
+    let area = fixed_rect_in_center(f.area(), 64, 9);
+    let dim = Style::default().fg(theme.muted).add_modifier(Modifier::DIM);
+    let hint = Style::default()
+        .fg(theme.hint)
+        .add_modifier(Modifier::ITALIC);
+
+    let mut tabs = Vec::new();
+    for (i, name) in std::iter::once("Recent")
+        .chain(CATEGORIES.iter().map(|(name, _)| *name))
+        .enumerate()
+    {
+        if i > 0 {
+            tabs.push(Span::raw(" "));
+        }
+        if i == picker.category && picker.query.is_empty() {
+            tabs.push(Span::styled(name, theme.focused(theme.button_focus)));
+        } else {
+            tabs.push(Span::styled(name, dim));
+        }
+    }
+
+    let search = if picker.query.is_empty() {
+        Line::from(Span::styled("type to search", hint))
+    } else {
+        Line::from(vec![
+            Span::styled("search: ", Style::default().fg(theme.muted)),
+            Span::styled(picker.query.clone(), Style::default().fg(theme.text)),
+        ])
+    };
+    let (row, name) = if items.is_empty() {
+        let empty = if picker.query.is_empty() {
+            "nothing used yet"
+        } else {
+            "no matches"
+        };
+        (Line::from(Span::styled(empty, hint)), Line::raw(""))
+    } else {
+        let glyphs: Vec<&str> = items.iter().map(|(_, e)| *e).collect();
+        (
+            carousel(&glyphs, picker.selected, 5, theme),
+            Line::from(Span::styled(
+                format!(":{}:", items[picker.selected].0),
+                Style::default().fg(theme.text),
+            )),
+        )
+    };
+
+    let lines = vec![
+        Line::from(tabs),
+        search,
+        Line::raw(""),
+        row,
+        name,
+        Line::raw(""),
+        Line::from(Span::styled(
+            "Left/Right: pick  Tab: category  Enter: insert  Esc: close",
+            hint,
+        )),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Emoji ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
@@ -0,0 +1,116 @@
+use std::collections::hash_map::RandomState;
+use std::fs::OpenOptions;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// $VISUAL, then $EDITOR, then whatever is always there. the variable can carry arguments
+// ("code --wait"), so it gets split on spaces
+fn editor_command() -> Vec<String> {
+    let editor = std::env::var("VISUAL")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        });
+    editor.split_whitespace().map(str::to_string).collect()
+}
+
+// a new file only we can read, under a name nobody can guess ahead of time. create_new means
+// a file or a symlink someone left at that path makes us pick another name, not write through it
+fn create_temp_file(text: &str) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir();
+    let mut last_error = None;
+    for _ in 0..16 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        let path = dir.join(format!(
+            "reetui-message-{}-{:016x}.md",
+            std::process::id(),
+            hasher.finish()
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                let written = file.write_all(text.as_bytes());
+                if let Err(e) = written {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(format!("Couldn't write {}: {e}", path.display()));
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => last_error = Some(e),
+            Err(e) => return Err(format!("Couldn't create {}: {e}", path.display())),
+        }
+    }
+    Err(format!(
+        "Couldn't create a temp file in {}: {}",
+        dir.display(),
+        last_error.map_or("no luck".to_string(), |e| e.to_string())
+    ))
+}
+
+// writes `text` to a temp file, waits for the editor to close and reads it back. the terminal
+// has to be handed over before calling this
+pub fn edit(text: &str) -> Result<String, String> {
+    let path = create_temp_file(text)?;
+
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => return Err(format!("{} exited with {status}", command[0])),
+        Err(e) => return Err(format!("Couldn't start {}: {e}", command[0])),
+    }
+    // editors like to end the file with a newline nobody typed
+    edited
+        .map(|text| text.trim_end_matches(['\n', '\r']).to_string())
+        .map_err(|e| format!("Couldn't read the message back: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_files_are_new_and_private() {
+        let first = create_temp_file("hi").expect("temp file");
+        let second = create_temp_file("hi").expect("temp file");
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_to_string(&first).ok().as_deref(), Some("hi"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&first)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = std::fs::remove_file(first);
+        let _ = std::fs::remove_file(second);
+    }
+}
@@ -0,0 +1,138 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::action::{Action, Effect};
+use crate::app::App;
+use crate::auth_tui::fixed_rect_in_center;
+use crate::glyphs::GlyphSet;
+
+// shown once on the first run (and on /glyphs), every row is drawn with its own glyphs so
+// whichever one doesn't turn into boxes is the right answer. moving previews it
+pub struct GlyphPicker {
+    pub selected: usize,
+    pub previous: GlyphSet,
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    let Event::Key(KeyEvent { code, .. }) = evt else {
+        return None;
+    };
+    let action = match code {
+        KeyCode::Up | KeyCode::BackTab | KeyCode::Char('k') => Action::PickerPrev,
+        KeyCode::Down | KeyCode::Tab | KeyCode::Char('j') => Action::PickerNext,
+        KeyCode::Char(c @ '1'..='3') => {
+            return Some(Action::PickGlyphs(GlyphSet::ALL[c as usize - '1' as usize]))
+        }
+        KeyCode::Enter => Action::PickerConfirm,
+        KeyCode::Esc | KeyCode::Char('q') => Action::PickerCancel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    if let Action::OpenGlyphPicker = action {
+        app.glyph_picker = Some(GlyphPicker {
+            selected: GlyphSet::ALL
+                .iter()
+                .position(|s| *s == app.glyph_set)
+                .unwrap_or(0),
+            previous: app.glyph_set,
+        });
+        return vec![];
+    }
+    let Some(picker) = app.glyph_picker.as_mut() else {
+        return vec![];
+    };
+    let len = GlyphSet::ALL.len();
+    match action {
+        Action::PickerPrev => picker.selected = (picker.selected + len - 1) % len,
+        Action::PickerNext => picker.selected = (picker.selected + 1) % len,
+        Action::PickGlyphs(set) => {
+            app.glyph_picker = None;
+            app.set_glyphs(set);
+            return vec![Effect::SaveGlyphs(set)];
+        }
+        Action::PickerConfirm => {
+            app.glyph_picker = None;
+            return vec![Effect::SaveGlyphs(app.glyph_set)];
+        }
+        // keep what was there, the first run asks again next time
+        Action::PickerCancel => {
+            let previous = picker.previous;
+            app.glyph_picker = None;
+            app.set_glyphs(previous);
+            return vec![];
+        }
+        _ => return vec![],
+    }
+    let set = GlyphSet::ALL[picker.selected];
+    app.set_glyphs(set);
+    vec![]
+}
+
+pub fn ui(f: &mut Frame, app: &App) {
+    let Some(picker) = &app.glyph_picker else {
+        return;
+    };
+    let theme = &app.theme;
+    let area = fixed_rect_in_center(f.area(), 40, GlyphSet::ALL.len() as u16 + 5);
+
+    let mut lines = vec![Line::from(Span::styled(
+        "Which row shows icons, not boxes?",
+        Style::default().fg(theme.text),
+    ))];
+    for (i, set) in GlyphSet::ALL.iter().enumerate() {
+        let glyphs = set.glyphs();
+        let sample = glyphs
+            .icons
+            .iter()
+            .take(4)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let text = format!(
+            "{} {}. {:<9} {} {} {}",
+            if i == picker.selected { ">" } else { " " },
+            i + 1,
+            set.name(),
+            sample,
+            glyphs.avatar,
+            glyphs.system_icon
+        );
+        let style = if i == picker.selected {
+            theme.focused(theme.button_focus)
+        } else {
+            Style::default().fg(theme.text)
+        };
+        lines.push(Line::from(Span::styled(text, style)));
+    }
+    lines.push(Line::raw(""));
+    lines.push(Line::from(Span::styled(
+        "Enter: keep  Esc: cancel",
+        Style::default()
+            .fg(theme.hint)
+            .add_modifier(Modifier::ITALIC),
+    )));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Glyphs ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
@@ -0,0 +1,221 @@
+use ratatui::symbols::{border, scrollbar};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+use crate::config::{self, Dirs};
+
+// which kind of glyphs the terminal font can actually draw. nerd font icons live in the
+// private use area and show up as boxes everywhere else
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GlyphSet {
+    NerdFont,
+    Unicode,
+    Ascii,
+}
+
+pub struct Glyphs {
+    pub icons: &'static [&'static str],
+    // for users without an icon
+    pub avatar: &'static str,
+    // for messages the client makes up itself
+    pub system_icon: &'static str,
+    pub border: border::Set,
+    // "┌ " in front of a message header and "│ " in front of every body line
+    pub header_prefix: &'static str,
+    pub body_prefix: &'static str,
+    // drawn over the right border of a box that has more than fits
+    pub scrollbar: scrollbar::Set,
+}
+
+const NERD_FONT: Glyphs = Glyphs {
+    icons: &[
+        "󰱨",
+        "󰱩",
+        "󰱫",
+        "󰄛",
+        "󰊖",
+        "󱃞",
+        "󰱬",
+        "󰱮",
+        "󰱯",
+        "󰱰",
+        "󰽌",
+        "󰱱",
+        "󰱲",
+        "󱈔",
+        "󰱸",
+        "󰇳",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "󱢯",
+        "",
+        "󰇟",
+        "󰞅",
+        "🤓👆",
+        "🗿",
+        "🧑‍💻",
+        "󰱴",
+        "󰇷",
+        "󱕼",
+        "󰇹",
+        "",
+        "",
+        "",
+        "",
+        "",
+        "󰇶",
+        "󰻟",
+        "",
+        "",
+        "󰣑",
+        "󰢮",
+        "",
+    ],
+    avatar: "󰬌",
+    system_icon: "󰚩",
+    border: border::ROUNDED,
+    header_prefix: "┌ ",
+    body_prefix: "│ ",
+    scrollbar: scrollbar::Set {
+        track: "│",
+        thumb: "┃",
+        begin: "│",
+        end: "│",
+    },
+};
+
+const UNICODE: Glyphs = Glyphs {
+    icons: &[
+        "😀",
+        "😎",
+        "🤓",
+        "🥳",
+        "😺",
+        "🐶",
+        "🦊",
+        "🐸",
+        "🐼",
+        "🐧",
+        "🦄",
+        "🐙",
+        "🦀",
+        "🌵",
+        "🍕",
+        "☕",
+        "🎮",
+        "🎧",
+        "🚀",
+        "⭐",
+        "🔥",
+        "🌙",
+        "👾",
+        "🤖",
+        "💀",
+        "🗿",
+        "🤓👆",
+        "🧑‍💻",
+    ],
+    avatar: "👤",
+    system_icon: "🤖",
+    border: border::ROUNDED,
+    header_prefix: "┌ ",
+    body_prefix: "│ ",
+    scrollbar: scrollbar::Set {
+        track: "│",
+        thumb: "┃",
+        begin: "│",
+        end: "│",
+    },
+};
+
+const ASCII: Glyphs = Glyphs {
+    icons: &[
+        ":)", ":D", ";)", ":P", "xD", ":3", "<3", "^_^", "o_O", "B)", ">:)", "-_-", "T_T", "UwU",
+        "@_@", "\\o/",
+    ],
+    avatar: "@",
+    system_icon: "#",
+    border: border::Set {
+        top_left: "+",
+        top_right: "+",
+        bottom_left: "+",
+        bottom_right: "+",
+        vertical_left: "|",
+        vertical_right: "|",
+        horizontal_top: "-",
+        horizontal_bottom: "-",
+    },
+    header_prefix: "+ ",
+    body_prefix: "| ",
+    scrollbar: scrollbar::Set {
+        track: "|",
+        thumb: "#",
+        begin: "|",
+        end: "|",
+    },
+};
+
+impl GlyphSet {
+    pub const ALL: [GlyphSet; 3] = [GlyphSet::NerdFont, GlyphSet::Unicode, GlyphSet::Ascii];
+
+    pub fn glyphs(self) -> &'static Glyphs {
+        match self {
+            GlyphSet::NerdFont => &NERD_FONT,
+            GlyphSet::Unicode => &UNICODE,
+            GlyphSet::Ascii => &ASCII,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GlyphSet::NerdFont => "nerdfont",
+            GlyphSet::Unicode => "unicode",
+            GlyphSet::Ascii => "ascii",
+        }
+    }
+}
+
+fn is_private_use(c: char) -> bool {
+    matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{FFFFD}' | '\u{100000}'..='\u{10FFFD}')
+}
+
+// other people still pick nerd font icons, without the font those would be boxes
+pub fn display_icon(icon: &str, set: GlyphSet) -> &str {
+    if set != GlyphSet::NerdFont && icon.chars().any(is_private_use) {
+        set.glyphs().avatar
+    } else {
+        icon
+    }
+}
+
+// what the probe settled on last time, so it only ever runs once
+pub fn cached(dirs: &Dirs) -> Option<GlyphSet> {
+    let data = std::fs::read_to_string(dirs.data_file("glyphs.json")?).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+pub fn save_cache(dirs: &Dirs, set: GlyphSet) -> Result<(), String> {
+    let path = dirs.data_file("glyphs.json").ok_or("No data directory")?;
+    let data = serde_json::to_string(&set).map_err(|e| e.to_string())?;
+    config::write_file(&path, &data)
+}
+
+// prints an emoji in the corner and asks the terminal where the cursor ended up. two columns
+// means it knows about wide characters, anything else (or no answer) means stick to ascii.
+// there's no way to ask whether a font has nerd font icons, the picker asks the user for that
+pub fn probe_wide_chars(out: &mut impl Write) -> bool {
+    let printed = crossterm::execute!(
+        out,
+        crossterm::cursor::MoveTo(0, 0),
+        crossterm::style::Print("😀")
+    );
+    printed.is_ok() && matches!(crossterm::cursor::position(), Ok((2, 0)))
+}
@@ -0,0 +1,229 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, MouseEventKind};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::action::{Action, Effect};
+use crate::app::{App, Page};
+use crate::auth_tui::fixed_rect_in_center;
+use crate::commands::COMMANDS;
+use crate::keymap::Context;
+use crate::text_layout::str_width;
+
+// F1, ? or /help. built from the keymap and COMMANDS every time it's drawn, so a rebind or a
+// new command shows up without anyone touching this
+pub struct Help {
+    pub query: String,
+    pub scroll: usize,
+    // how many rows fit last time it was drawn, for clamping the scroll
+    pub page_rows: usize,
+}
+
+impl Help {
+    pub fn new(query: &str) -> Self {
+        Help {
+            query: query.to_string(),
+            scroll: 0,
+            page_rows: 0,
+        }
+    }
+}
+
+// (title, [(keys, what it does)])
+type Section = (String, Vec<(String, String)>);
+
+fn sections(app: &App, query: &str) -> Vec<Section> {
+    // this page's keys first, they're the ones being looked for
+    let contexts: &[Context] = match app.page {
+        Page::Auth => &[Context::Auth, Context::AuthButtons, Context::AuthIcon],
+        Page::Home => &[Context::Home],
+        Page::Chat => &[Context::Chat],
+    };
+    let mut sections: Vec<Section> = contexts
+        .iter()
+        .chain([&Context::Global])
+        .map(|context| {
+            let rows = app
+                .keymap
+                .describe(*context)
+                .into_iter()
+                .map(|(what, keys)| (keys.join(", "), what.to_string()))
+                .collect();
+            (context.title().to_string(), rows)
+        })
+        .collect();
+    // commands only do anything from the composer
+    if matches!(app.page, Page::Chat) {
+        sections.push((
+            "Commands".to_string(),
+            COMMANDS
+                .iter()
+                .map(|c| (c.usage(), c.help.to_string()))
+                .collect(),
+        ));
+    }
+
+    let query = query.to_lowercase();
+    for (_, rows) in &mut sections {
+        rows.retain(|(keys, what)| {
+            keys.to_lowercase().contains(&query) || what.to_lowercase().contains(&query)
+        });
+    }
+    sections.retain(|(_, rows)| !rows.is_empty());
+    sections
+}
+
+fn line_count(app: &App, query: &str) -> usize {
+    let sections = sections(app, query);
+    // a title and a blank line after each section
+    sections.iter().map(|(_, rows)| rows.len() + 2).sum()
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    let code = match evt {
+        Event::Key(KeyEvent { code, .. }) => code,
+        Event::Mouse(mouse) => {
+            return match mouse.kind {
+                MouseEventKind::ScrollUp => Some(Action::HelpScroll(-3)),
+                MouseEventKind::ScrollDown => Some(Action::HelpScroll(3)),
+                _ => None,
+            }
+        }
+        _ => return None,
+    };
+    let action = match code {
+        KeyCode::Esc | KeyCode::F(1) => Action::CloseHelp,
+        KeyCode::Up => Action::HelpScroll(-1),
+        KeyCode::Down => Action::HelpScroll(1),
+        KeyCode::PageUp => Action::HelpScroll(-10),
+        KeyCode::PageDown => Action::HelpScroll(10),
+        KeyCode::Backspace => Action::HelpErase,
+        KeyCode::Char(c) => Action::HelpType(c),
+        _ => return None,
+    };
+    Some(action)
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    if let Action::OpenHelp = action {
+        app.help = Some(Help::new(""));
+        return vec![];
+    }
+    let lines = match &app.help {
+        Some(help) => line_count(app, &help.query),
+        None => return vec![],
+    };
+    let Some(help) = &mut app.help else {
+        return vec![];
+    };
+    match action {
+        Action::CloseHelp => app.help = None,
+        Action::HelpType(c) => {
+            help.query.push(c);
+            help.scroll = 0;
+        }
+        Action::HelpErase => {
+            help.query.pop();
+            help.scroll = 0;
+        }
+        Action::HelpScroll(by) => {
+            let max = lines.saturating_sub(help.page_rows);
+            help.scroll = help.scroll.saturating_add_signed(by).min(max);
+        }
+        _ => {}
+    }
+    vec![]
+}
+
+pub fn ui(f: &mut Frame, app: &mut App) {
+    let Some(query) = app.help.as_ref().map(|h| h.query.clone()) else {
+        return;
+    };
+    let theme = &app.theme;
+    let sections = sections(app, &query);
+    let area = fixed_rect_in_center(
+        f.area(),
+        f.area().width.saturating_sub(8).min(90),
+        f.area().height.saturating_sub(4),
+    );
+    let hint = Style::default()
+        .fg(theme.hint)
+        .add_modifier(Modifier::ITALIC);
+
+    let keys_width = sections
+        .iter()
+        .flat_map(|(_, rows)| rows.iter().map(|(keys, _)| str_width(keys)))
+        .max()
+        .unwrap_or(0)
+        .min(area.width as usize / 2);
+    let mut lines = Vec::new();
+    for (title, rows) in sections {
+        lines.push(Line::from(Span::styled(
+            title,
+            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        )));
+        for (keys, what) in rows {
+            let pad = keys_width.saturating_sub(str_width(&keys));
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {keys}{}  ", " ".repeat(pad)),
+                    Style::default().fg(theme.button_focus),
+                ),
+                Span::styled(what, Style::default().fg(theme.text)),
+            ]));
+        }
+        lines.push(Line::raw(""));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled("nothing matches", hint)));
+    }
+
+    // the search line and the hint take two rows, the border two more
+    let page_rows = area.height.saturating_sub(4) as usize;
+    let help = app.help.as_mut().expect("checked above");
+    help.page_rows = page_rows;
+    help.scroll = help.scroll.min(lines.len().saturating_sub(page_rows));
+    let shown: Vec<Line> = lines
+        .into_iter()
+        .skip(help.scroll)
+        .take(page_rows)
+        .collect();
+
+    let theme = &app.theme;
+    let search = if query.is_empty() {
+        Line::from(Span::styled("type to search", hint))
+    } else {
+        Line::from(vec![
+            Span::styled("search: ", Style::default().fg(theme.muted)),
+            Span::styled(query, Style::default().fg(theme.text)),
+        ])
+    };
+    let mut text = vec![search];
+    text.extend(shown);
+    while text.len() < page_rows + 1 {
+        text.push(Line::raw(""));
+    }
+    text.push(Line::from(Span::styled(
+        "Up/Down/PgUp/PgDn: scroll  Esc: close",
+        hint,
+    )));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Help ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
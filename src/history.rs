@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use crate::config::{self, Dirs};
+
+// how many sent messages we remember per server
+const MAX_HISTORY: usize = 1000;
+
+// shell style history of what was sent. Up/Down walk it from the composer's first/last row and
+// put the draft back when walking off the newest end, Ctrl+R searches it
+#[derive(Default)]
+pub struct History {
+    pub server: String,
+    entries: Vec<String>,
+    // which entry is in the composer right now, None while on the draft
+    browsing: Option<usize>,
+    draft: String,
+    pub search: Option<Search>,
+}
+
+pub struct Search {
+    pub query: String,
+    pub found: Option<usize>,
+    // what was in the composer when the search started, Esc puts it back
+    pub original: String,
+}
+
+// "isock.reetui.hackclub.app" for ws://isock.reetui.hackclub.app, safe as a file name
+pub fn server_key(url: &str) -> String {
+    let host = url.split("://").last().unwrap_or(url);
+    let host = host.split('/').next().unwrap_or(host);
+    host.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn history_path(dirs: &Dirs, server: &str) -> Option<PathBuf> {
+    dirs.data_file("history")
+        .map(|dir| dir.join(format!("{server}.json")))
+}
+
+pub fn save(dirs: &Dirs, server: &str, entries: &[String]) -> Result<(), String> {
+    let path = history_path(dirs, server).ok_or("No data directory")?;
+    let data = serde_json::to_string(entries).map_err(|e| e.to_string())?;
+    config::write_file(&path, &data)
+}
+
+impl History {
+    pub fn new(server: String) -> Self {
+        History {
+            server,
+            ..Default::default()
+        }
+    }
+
+    // a missing file is just an empty history
+    pub fn load(dirs: &Dirs, server: String) -> Result<Self, String> {
+        let mut history = History::new(server);
+        let Some(path) = history_path(dirs, &history.server).filter(|p| p.exists()) else {
+            return Ok(history);
+        };
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+        history.entries =
+            serde_json::from_str(&data).map_err(|e| format!("Invalid history: {e}"))?;
+        Ok(history)
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    // sending always goes back to a fresh draft
+    pub fn push(&mut self, entry: String) {
+        self.browsing = None;
+        self.draft.clear();
+        self.search = None;
+        if self.entries.last() == Some(&entry) {
+            return;
+        }
+        self.entries.push(entry);
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+    }
+
+    // one step older, `current` is what's in the composer so it can come back later
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        let index = match self.browsing {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(i) => i - 1,
+        };
+        self.browsing = Some(index);
+        Some(&self.entries[index])
+    }
+
+    // one step newer, past the newest entry is the draft
+    pub fn next(&mut self) -> Option<&str> {
+        let index = self.browsing?;
+        if index + 1 < self.entries.len() {
+            self.browsing = Some(index + 1);
+            Some(&self.entries[index + 1])
+        } else {
+            self.browsing = None;
+            Some(&self.draft)
+        }
+    }
+
+    pub fn start_search(&mut self, current: &str) {
+        self.search = Some(Search {
+            query: String::new(),
+            found: None,
+            original: current.to_string(),
+        });
+    }
+
+    // newest entry older than `before` with the query in it, case doesn't matter
+    fn find(&self, query: &str, before: usize) -> Option<usize> {
+        let query = query.to_lowercase();
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|e| e.to_lowercase().contains(&query))
+    }
+
+    // the query changed, look again from the newest end
+    pub fn search_for(&mut self, query: String) -> Option<&str> {
+        let found = self.find(&query, self.entries.len());
+        let search = self.search.as_mut()?;
+        search.query = query;
+        search.found = found;
+        found.map(|i| self.entries[i].as_str())
+    }
+
+    // Ctrl+R again, the next older match
+    pub fn search_older(&mut self) -> Option<&str> {
+        let search = self.search.as_ref()?;
+        let before = search.found.unwrap_or(self.entries.len());
+        let found = self.find(&search.query, before)?;
+        self.search.as_mut()?.found = Some(found);
+        Some(&self.entries[found])
+    }
+}
@@ -1,10 +1,10 @@
-use crate::app::App;
-use crate::Page;
+use crate::action::{Action, Effect};
+use crate::app::{App, Page};
+use crate::keymap::{self, Context};
 use crossterm::event::Event;
-use crossterm::event::KeyEvent;
 use ratatui::{
     layout::{Alignment, Rect},
-    style::{Color, Style},
+    style::Style,
     text::{Line, Span},
     widgets::{Clear, Paragraph},
     Frame,
@@ -28,13 +28,23 @@ const ART: &str = r#"
          ████████████                                                                                  
 "#;
 
-pub fn handle_event(evt: Event, app: &mut App) {
-    if let Event::Key(KeyEvent { .. }) = evt {
+// global keys still work here, anything else carries on to the chat
+pub fn map_event(evt: Event, app: &App) -> Option<Action> {
+    let Event::Key(key) = evt else {
+        return None;
+    };
+    keymap::bound(app, &[Context::Home], &key).or(Some(Action::Continue))
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    if let Action::Continue = action {
         app.page = Page::Chat;
     }
+    vec![]
 }
 
-pub fn ui(f: &mut Frame, _app: &App) {
+pub fn ui(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
     let area = f.area();
 
     let lines: Vec<Line> = ART.lines().map(|l| Line::from(Span::raw(l))).collect();
@@ -51,7 +61,9 @@ pub fn ui(f: &mut Frame, _app: &App) {
     };
 
     f.render_widget(Clear, art_area);
-    let ascii = Paragraph::new(lines).alignment(Alignment::Center);
+    let ascii = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.text));
     f.render_widget(ascii, art_area);
 
     let copilot_area = Rect {
@@ -62,7 +74,7 @@ pub fn ui(f: &mut Frame, _app: &App) {
     };
     let copilot = Paragraph::new("this app may contain code generated by copilot")
         .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::Gray));
+        .style(Style::default().fg(theme.hint));
     f.render_widget(copilot, copilot_area);
 
     let footer_area = Rect {
@@ -73,7 +85,7 @@ pub fn ui(f: &mut Frame, _app: &App) {
     };
     let footer_text = Paragraph::new("ReeTui inc.")
         .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::Green));
+        .style(Style::default().fg(theme.accent));
     f.render_widget(footer_text, footer_area);
 
     let press_area = Rect {
@@ -84,6 +96,6 @@ pub fn ui(f: &mut Frame, _app: &App) {
     };
     let top_text = Paragraph::new("Press any key to continue")
         .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::Gray));
+        .style(Style::default().fg(theme.hint));
     f.render_widget(top_text, press_area);
 }
@@ -0,0 +1,846 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::action::Action;
+use crate::app::{App, AuthMode};
+use crate::config::Dirs;
+
+// one key with its modifiers, the way keybindings.toml spells it ("ctrl+x", "shift+tab", "f2")
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+// where a binding applies. lookups go from the most specific context to global, so a page can
+// take a key global uses
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Context {
+    Global,
+    Auth,
+    // on the login form but not in a text field, so plain letters are free
+    AuthButtons,
+    AuthIcon,
+    Home,
+    Chat,
+}
+
+impl Context {
+    pub const ALL: [Context; 6] = [
+        Context::Global,
+        Context::Auth,
+        Context::AuthButtons,
+        Context::AuthIcon,
+        Context::Home,
+        Context::Chat,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Context::Global => "global",
+            Context::Auth => "auth",
+            Context::AuthButtons => "auth_buttons",
+            Context::AuthIcon => "auth_icon",
+            Context::Home => "home",
+            Context::Chat => "chat",
+        }
+    }
+
+    // for the help overlay
+    pub fn title(self) -> &'static str {
+        match self {
+            Context::Global => "Everywhere",
+            Context::Auth => "Login",
+            Context::AuthButtons => "Login, off the text fields",
+            Context::AuthIcon => "Login, on the icon",
+            Context::Home => "Welcome screen",
+            Context::Chat => "Chat",
+        }
+    }
+}
+
+// everything a key can be bound to, by the name keybindings.toml uses, and what the help
+// overlay says it does. "none" unbinds a key
+type MakeAction = fn() -> Action;
+
+const ACTIONS: &[(&str, &str, MakeAction)] = &[
+    ("quit", "Quit", || Action::Quit),
+    ("focus_next", "Next field", || Action::FocusNext),
+    ("focus_prev", "Previous field", || Action::FocusPrev),
+    ("login_mode", "Switch to login", || {
+        Action::SetAuthMode(AuthMode::Login)
+    }),
+    ("register_mode", "Switch to register", || {
+        Action::SetAuthMode(AuthMode::Register)
+    }),
+    (
+        "toggle_auth_mode",
+        "Switch between login and register",
+        || Action::ToggleAuthMode,
+    ),
+    ("submit", "Log in / register", || Action::Submit),
+    ("prev_icon", "Previous icon", || Action::PrevIcon),
+    ("next_icon", "Next icon", || Action::NextIcon),
+    ("send", "Send the message", || Action::SendMessage),
+    ("newline", "New line in the message", || {
+        Action::InsertNewline
+    }),
+    (
+        "backspace",
+        "Delete the character before the cursor",
+        || Action::Backspace,
+    ),
+    ("delete", "Delete the character under the cursor", || {
+        Action::Delete
+    }),
+    ("left", "Cursor left", || Action::CursorLeft),
+    ("right", "Cursor right", || Action::CursorRight),
+    ("up", "Cursor up, or the previous sent message", || {
+        Action::CursorUp
+    }),
+    ("down", "Cursor down, or the next sent message", || {
+        Action::CursorDown
+    }),
+    ("home", "Start of the line", || Action::CursorHome),
+    ("end", "End of the line", || Action::CursorEnd),
+    ("word_left", "Word left", || Action::CursorWordLeft),
+    ("word_right", "Word right", || Action::CursorWordRight),
+    ("delete_word_back", "Cut the word before the cursor", || {
+        Action::DeleteWordBack
+    }),
+    (
+        "delete_word_forward",
+        "Cut the word after the cursor",
+        || Action::DeleteWordForward,
+    ),
+    ("kill_to_start", "Cut to the start of the line", || {
+        Action::KillToStart
+    }),
+    ("kill_to_end", "Cut to the end of the line", || {
+        Action::KillToEnd
+    }),
+    ("yank", "Paste the last cut", || Action::Yank),
+    ("yank_pop", "Swap a paste for the cut before it", || {
+        Action::YankPop
+    }),
+    (
+        "transpose",
+        "Swap the two characters around the cursor",
+        || Action::Transpose,
+    ),
+    ("undo", "Undo", || Action::Undo),
+    ("redo", "Redo", || Action::Redo),
+    ("history_search", "Search sent messages", || {
+        Action::HistorySearch
+    }),
+    ("cancel", "Cancel the search or completion", || {
+        Action::CancelSearch
+    }),
+    ("complete", "Complete /commands, @names, :emoji:", || {
+        Action::Complete
+    }),
+    ("complete_prev", "Previous completion", || {
+        Action::CompletePrev
+    }),
+    (
+        "edit_externally",
+        "Write the message in $VISUAL / $EDITOR",
+        || Action::EditExternally,
+    ),
+    ("scroll_up", "Scroll the chat up", || Action::ScrollUp),
+    ("scroll_down", "Scroll the chat down", || Action::ScrollDown),
+    ("theme_picker", "Theme picker", || Action::OpenThemePicker),
+    ("glyph_picker", "Icon set picker", || {
+        Action::OpenGlyphPicker
+    }),
+    ("emoji_picker", "Emoji picker", || {
+        Action::OpenEmojiPicker(String::new())
+    }),
+    ("diagnostics", "Terminal diagnostics", || {
+        Action::OpenDiagnostics
+    }),
+    ("help", "This help", || Action::OpenHelp),
+    (
+        "toggle_mouse",
+        "Let go of the mouse for selecting text, or take it back",
+        || Action::ToggleMouse,
+    ),
+];
+
+const UNBOUND: &str = "none";
+
+const DEFAULTS: &[(Context, &str, &str)] = &[
+    (Context::Global, "f1", "help"),
+    (Context::Global, "f2", "theme_picker"),
+    (Context::Global, "f10", "toggle_mouse"),
+    (Context::Global, "f12", "diagnostics"),
+    (Context::Global, "ctrl+c", "quit"),
+    (Context::Auth, "tab", "focus_next"),
+    (Context::Auth, "down", "focus_next"),
+    (Context::Auth, "shift+tab", "focus_prev"),
+    (Context::Auth, "up", "focus_prev"),
+    (Context::Auth, "ctrl+t", "toggle_auth_mode"),
+    (Context::Auth, "enter", "submit"),
+    (Context::Auth, "backspace", "backspace"),
+    // one line fields, so just moving along them and the deleting readline keys
+    (Context::Auth, "left", "left"),
+    (Context::Auth, "right", "right"),
+    (Context::Auth, "ctrl+w", "delete_word_back"),
+    (Context::Auth, "ctrl+u", "kill_to_start"),
+    (Context::AuthButtons, "shift+l", "login_mode"),
+    (Context::AuthButtons, "shift+r", "register_mode"),
+    (Context::AuthButtons, "shift+q", "quit"),
+    (Context::AuthButtons, "?", "help"),
+    (Context::AuthIcon, "left", "prev_icon"),
+    (Context::AuthIcon, "right", "next_icon"),
+    (Context::Home, "?", "help"),
+    (Context::Chat, "enter", "send"),
+    (Context::Chat, "shift+enter", "newline"),
+    // for terminals that send Shift+Enter as a plain Enter. Ctrl+J is a raw line feed
+    (Context::Chat, "alt+enter", "newline"),
+    (Context::Chat, "ctrl+j", "newline"),
+    (Context::Chat, "backspace", "backspace"),
+    (Context::Chat, "delete", "delete"),
+    (Context::Chat, "left", "left"),
+    (Context::Chat, "right", "right"),
+    (Context::Chat, "up", "up"),
+    (Context::Chat, "down", "down"),
+    (Context::Chat, "home", "home"),
+    (Context::Chat, "end", "end"),
+    (Context::Chat, "ctrl+a", "home"),
+    (Context::Chat, "ctrl+e", "end"),
+    (Context::Chat, "ctrl+b", "left"),
+    (Context::Chat, "ctrl+f", "right"),
+    (Context::Chat, "alt+b", "word_left"),
+    (Context::Chat, "alt+f", "word_right"),
+    (Context::Chat, "ctrl+left", "word_left"),
+    (Context::Chat, "ctrl+right", "word_right"),
+    (Context::Chat, "alt+left", "word_left"),
+    (Context::Chat, "alt+right", "word_right"),
+    (Context::Chat, "ctrl+d", "delete"),
+    (Context::Chat, "alt+d", "delete_word_forward"),
+    (Context::Chat, "ctrl+delete", "delete_word_forward"),
+    (Context::Chat, "ctrl+w", "delete_word_back"),
+    (Context::Chat, "ctrl+backspace", "delete_word_back"),
+    (Context::Chat, "alt+backspace", "delete_word_back"),
+    (Context::Chat, "ctrl+u", "kill_to_start"),
+    (Context::Chat, "ctrl+k", "kill_to_end"),
+    (Context::Chat, "ctrl+y", "yank"),
+    (Context::Chat, "alt+y", "yank_pop"),
+    (Context::Chat, "ctrl+t", "transpose"),
+    (Context::Chat, "ctrl+z", "undo"),
+    // Ctrl+_ (and Ctrl+/ on most terminals) is a 0x1f byte, which crossterm reads as Ctrl+7
+    (Context::Chat, "ctrl+_", "undo"),
+    (Context::Chat, "ctrl+7", "undo"),
+    (Context::Chat, "ctrl+/", "undo"),
+    (Context::Chat, "ctrl+shift+z", "redo"),
+    (Context::Chat, "ctrl+r", "history_search"),
+    (Context::Chat, "ctrl+g", "cancel"),
+    (Context::Chat, "esc", "cancel"),
+    (Context::Chat, "tab", "complete"),
+    (Context::Chat, "shift+tab", "complete_prev"),
+    (Context::Chat, "ctrl+x ctrl+e", "edit_externally"),
+    (Context::Chat, "alt+e", "emoji_picker"),
+    (Context::Chat, "ctrl+up", "scroll_up"),
+    (Context::Chat, "ctrl+down", "scroll_down"),
+];
+
+impl KeyPress {
+    // terminals disagree on whether Shift+L is 'L', or 'l' with shift, or 'L' with shift, so
+    // both sides of a lookup get squashed into one spelling first
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        KeyPress { code, modifiers }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        // "ctrl++" and "+" are the plus key
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None if s == "+" => ("", "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        // a single letter keeps its case, "L" is shift+l
+        let name = if key.chars().count() == 1 {
+            key.to_string()
+        } else {
+            key.to_lowercase()
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for m in mods.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match m.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "option" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{m}' in '{s}'")),
+            };
+        }
+        let code = match name.as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            f if f.starts_with('f') && f.len() > 1 && f[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(f[1..].parse().unwrap_or(1))
+            }
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap_or(' ')),
+            _ => return Err(format!("unknown key '{key}' in '{s}'")),
+        };
+        Ok(KeyPress::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        // only non-letters still have shift, letters got it folded into the case
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) if c.is_uppercase() => write!(f, "shift+{}", c.to_lowercase()),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::BackTab => write!(f, "shift+tab"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+pub fn show_keys(keys: &[KeyPress]) -> String {
+    keys.iter()
+        .map(KeyPress::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_keys(s: &str) -> Result<Vec<KeyPress>, String> {
+    let keys = s
+        .split_whitespace()
+        .map(KeyPress::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(keys)
+}
+
+fn action_named(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, _, f)| f())
+}
+
+pub enum Lookup {
+    Action(Action),
+    // the first part of a chord, waiting for the rest
+    Pending(KeyPress),
+    // a chord that went nowhere, or a key bound to "none"
+    Swallowed(String),
+    // not bound, the page can do its own thing with it (typing letters)
+    Unbound,
+}
+
+enum Found {
+    Name(&'static str),
+    Chord,
+    Nothing,
+}
+
+#[derive(Default)]
+pub struct Keymap {
+    bindings: HashMap<Context, HashMap<Vec<KeyPress>, &'static str>>,
+    // keybindings.toml, when there is one
+    pub source: Option<PathBuf>,
+    // what the startup check found, shown in /diag and once as an error
+    pub problems: Vec<String>,
+}
+
+// static names for what came out of the file, so bindings can stay &'static str
+fn static_name(name: &str) -> Option<&'static str> {
+    if name == UNBOUND {
+        return Some(UNBOUND);
+    }
+    ACTIONS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(n, _, _)| *n)
+}
+
+impl Keymap {
+    fn defaults() -> Self {
+        let mut keymap = Keymap::default();
+        for (context, keys, action) in DEFAULTS {
+            let keys = parse_keys(keys).expect("default keybinding");
+            keymap
+                .bindings
+                .entry(*context)
+                .or_default()
+                .insert(keys, action);
+        }
+        keymap
+    }
+
+    // the defaults with keybindings.toml on top, then the conflict check. a broken file still
+    // gives a working keymap, the problems just get listed
+    pub fn load(dirs: &Dirs) -> Self {
+        let mut keymap = Keymap::defaults();
+        if let Some(path) = dirs.config_file("keybindings.toml").filter(|p| p.exists()) {
+            match std::fs::read_to_string(&path) {
+                Ok(data) => keymap.apply_file(&data),
+                Err(e) => keymap
+                    .problems
+                    .push(format!("Couldn't read {}: {e}", path.display())),
+            }
+            keymap.source = Some(path);
+        }
+        keymap.check();
+        keymap
+    }
+
+    // [chat]
+    // "ctrl+s" = "send"
+    // "ctrl+x ctrl+e" = "edit_externally"
+    // "ctrl+r" = "none"
+    fn apply_file(&mut self, data: &str) {
+        let file: HashMap<String, HashMap<String, String>> = match toml::from_str(data) {
+            Ok(file) => file,
+            Err(e) => {
+                self.problems
+                    .push(format!("Invalid keybindings.toml: {}", e.message()));
+                return;
+            }
+        };
+        for (section, entries) in file {
+            let Some(context) = Context::ALL.into_iter().find(|c| c.name() == section) else {
+                self.problems.push(format!("[{section}] isn't a context"));
+                continue;
+            };
+            // two spellings of one key in the same section
+            let mut seen: HashMap<Vec<KeyPress>, String> = HashMap::new();
+            for (keys, action) in entries {
+                let parsed = match parse_keys(&keys) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        self.problems.push(format!("[{section}] {e}"));
+                        continue;
+                    }
+                };
+                let Some(action) = static_name(&action) else {
+                    self.problems
+                        .push(format!("[{section}] \"{keys}\": no action called {action}"));
+                    continue;
+                };
+                if let Some(other) = seen.insert(parsed.clone(), keys.clone()) {
+                    self.problems.push(format!(
+                        "[{section}] \"{keys}\" and \"{other}\" are the same key"
+                    ));
+                }
+                self.bindings
+                    .entry(context)
+                    .or_default()
+                    .insert(parsed, action);
+            }
+        }
+    }
+
+    // a key that starts a chord can't also do something by itself, one of the two never
+    // gets a turn. each context is checked with global underneath it, the way lookups see it:
+    // the context's own keys go first, so its chord beats a global key and its key beats a
+    // global chord
+    fn check(&mut self) {
+        for context in Context::ALL {
+            let mut merged: HashMap<&Vec<KeyPress>, (&'static str, Context)> = HashMap::new();
+            for from in [Context::Global, context] {
+                for (keys, action) in self.bindings.get(&from).into_iter().flatten() {
+                    merged.insert(keys, (action, from));
+                }
+            }
+            let mut problems = Vec::new();
+            for (keys, &(action, from)) in &merged {
+                if action == UNBOUND {
+                    continue;
+                }
+                for (longer, &(other, other_from)) in &merged {
+                    if longer.len() <= keys.len() || !longer.starts_with(keys) || other == UNBOUND {
+                        continue;
+                    }
+                    // both from global, that's global's own problem
+                    if context != Context::Global
+                        && from == Context::Global
+                        && other_from == Context::Global
+                    {
+                        continue;
+                    }
+                    let show = |keys: &[KeyPress], action, from: Context| {
+                        if from == context {
+                            format!("{} ({action})", show_keys(keys))
+                        } else {
+                            format!("{} ({action}, from {})", show_keys(keys), from.name())
+                        }
+                    };
+                    let (short, long) = (show(keys, action, from), show(longer, other, other_from));
+                    problems.push(
+                        if from == Context::Global && other_from != Context::Global {
+                            format!("[{}] {long} hides {short}", context.name())
+                        } else {
+                            format!("[{}] {short} hides {long}", context.name())
+                        },
+                    );
+                }
+            }
+            problems.sort();
+            self.problems.append(&mut problems);
+        }
+    }
+
+    // what `sequence` comes to in `contexts` then global, the first context that has it or a
+    // chord starting with it wins
+    fn find(&self, contexts: &[Context], sequence: &[KeyPress]) -> Found {
+        for context in contexts.iter().chain([&Context::Global]) {
+            let Some(bindings) = self.bindings.get(context) else {
+                continue;
+            };
+            if let Some(name) = bindings.get(sequence) {
+                return Found::Name(name);
+            }
+            let starts_chord = bindings.iter().any(|(keys, action)| {
+                keys.len() > sequence.len() && keys.starts_with(sequence) && *action != UNBOUND
+            });
+            if starts_chord {
+                return Found::Chord;
+            }
+        }
+        Found::Nothing
+    }
+
+    // `contexts` goes from the most specific to the least, global is always tried last
+    pub fn lookup(&self, contexts: &[Context], pending: &[KeyPress], key: &KeyEvent) -> Lookup {
+        let press = KeyPress::new(key.code, key.modifiers);
+        let mut sequence = pending.to_vec();
+        sequence.push(press);
+        match self.find(contexts, &sequence) {
+            Found::Name(UNBOUND) => Lookup::Swallowed(String::new()),
+            Found::Name(name) => match action_named(name) {
+                Some(action) => Lookup::Action(action),
+                None => Lookup::Swallowed(String::new()),
+            },
+            Found::Chord => Lookup::Pending(press),
+            Found::Nothing if pending.is_empty() => Lookup::Unbound,
+            Found::Nothing => {
+                Lookup::Swallowed(format!("{} isn't bound to anything", show_keys(&sequence)))
+            }
+        }
+    }
+
+    // what's bound to `action` in `contexts` (or global), for hints, shortest first
+    pub fn keys_for(&self, contexts: &[Context], action: &str) -> Vec<String> {
+        let mut contexts = contexts.to_vec();
+        contexts.push(Context::Global);
+        self.keys_in(&contexts, action)
+    }
+
+    // every action with a key in `context` and the keys, (description, keys) in ACTIONS order
+    pub fn describe(&self, context: Context) -> Vec<(&'static str, Vec<String>)> {
+        ACTIONS
+            .iter()
+            .map(|(name, what, _)| (*what, self.keys_in(&[context], name)))
+            .filter(|(_, keys)| !keys.is_empty())
+            .collect()
+    }
+
+    // only keys that really end up at `action` typed in `contexts`, not ones a more specific
+    // context took over or a chord sits in front of
+    fn keys_in(&self, contexts: &[Context], action: &str) -> Vec<String> {
+        let mut keys: Vec<Vec<KeyPress>> = contexts
+            .iter()
+            .filter_map(|c| self.bindings.get(c))
+            .flat_map(|b| b.iter())
+            .filter(|(_, a)| **a == action)
+            .map(|(k, _)| k.clone())
+            .filter(|k| {
+                (1..k.len()).all(|n| matches!(self.find(contexts, &k[..n]), Found::Chord))
+                    && matches!(self.find(contexts, k), Found::Name(a) if a == action)
+            })
+            .collect();
+        keys.sort_by_key(|k| (k.len(), show_keys(k).len(), show_keys(k)));
+        keys.dedup();
+        keys.iter().map(|k| show_keys(k)).collect()
+    }
+}
+
+// the keymap's say on a key, None when it's unbound and the page can do its own thing
+pub fn bound(app: &App, contexts: &[Context], key: &KeyEvent) -> Option<Action> {
+    match app.keymap.lookup(contexts, &app.pending_keys, key) {
+        Lookup::Action(action) => Some(action),
+        Lookup::Pending(key) => Some(Action::ChordPrefix(key)),
+        Lookup::Swallowed(why) => Some(Action::Unbound(why)),
+        Lookup::Unbound => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_file(file: &str) -> Keymap {
+        let mut keymap = Keymap::defaults();
+        keymap.apply_file(file);
+        keymap.check();
+        keymap
+    }
+
+    fn press(s: &str) -> KeyEvent {
+        let key = KeyPress::parse(s).unwrap();
+        KeyEvent::new(key.code, key.modifiers)
+    }
+
+    #[test]
+    fn parsing() {
+        let chord = parse_keys("ctrl+x  ctrl+e").unwrap();
+        assert_eq!(
+            chord,
+            [
+                KeyPress::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
+                KeyPress::new(KeyCode::Char('e'), KeyModifiers::CONTROL),
+            ]
+        );
+        assert_eq!(show_keys(&chord), "ctrl+x ctrl+e");
+        // the spellings of one key all come out the same
+        assert_eq!(KeyPress::parse("L"), KeyPress::parse("shift+l"));
+        assert_eq!(KeyPress::parse("Control+x"), KeyPress::parse("ctrl+x"));
+        assert_eq!(KeyPress::parse("shift+tab"), KeyPress::parse("backtab"));
+        assert_eq!(
+            KeyPress::parse("ctrl++"),
+            Ok(KeyPress::new(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(KeyPress::parse("F12").map(|k| k.code), Ok(KeyCode::F(12)));
+        assert_eq!(KeyPress::parse("shift+l").unwrap().to_string(), "shift+l");
+        // and what isn't a key
+        assert!(KeyPress::parse("hyper+x").is_err());
+        assert!(KeyPress::parse("ctrl+nope").is_err());
+        assert!(parse_keys("  ").is_err());
+        assert!(parse_keys("ctrl+x bogus").is_err());
+    }
+
+    #[test]
+    fn the_defaults_are_fine() {
+        assert_eq!(with_file("").problems, Vec::<String>::new());
+    }
+
+    #[test]
+    fn file_mistakes_are_listed() {
+        let keymap = with_file(
+            r#"
+            [nowhere]
+            "f3" = "help"
+            [chat]
+            "ctrl+nope" = "send"
+            "ctrl+s" = "fly"
+            "#,
+        );
+        assert_eq!(keymap.problems.len(), 3);
+        assert!(keymap.problems.iter().any(|p| p.contains("[nowhere]")));
+        assert!(keymap
+            .problems
+            .iter()
+            .any(|p| p.contains("unknown key 'nope'")));
+        assert!(keymap
+            .problems
+            .iter()
+            .any(|p| p.contains("no action called fly")));
+        // the rest of the keymap still works
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[], &press("enter")),
+            Lookup::Action(Action::SendMessage)
+        ));
+
+        assert!(with_file("not toml [").problems[0].starts_with("Invalid keybindings.toml"));
+    }
+
+    #[test]
+    fn rebinding_and_unbinding() {
+        let keymap = with_file(
+            r#"
+            [chat]
+            "ctrl+s" = "send"
+            "ctrl+r" = "none"
+            "#,
+        );
+        assert!(keymap.problems.is_empty());
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[], &press("ctrl+s")),
+            Lookup::Action(Action::SendMessage)
+        ));
+        // an unbound key is eaten rather than typed
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[], &press("ctrl+r")),
+            Lookup::Swallowed(why) if why.is_empty()
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[], &press("q")),
+            Lookup::Unbound
+        ));
+        assert_eq!(
+            keymap.keys_for(&[Context::Chat], "send"),
+            ["enter", "ctrl+s"]
+        );
+        assert!(keymap
+            .keys_for(&[Context::Chat], "history_search")
+            .is_empty());
+    }
+
+    #[test]
+    fn chords() {
+        let keymap = with_file("");
+        let ctrl_x = KeyPress::parse("ctrl+x").unwrap();
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[], &press("ctrl+x")),
+            Lookup::Pending(key) if key == ctrl_x
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[ctrl_x], &press("ctrl+e")),
+            Lookup::Action(Action::EditExternally)
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Context::Chat], &[ctrl_x], &press("q")),
+            Lookup::Swallowed(why) if why == "ctrl+x q isn't bound to anything"
+        ));
+        // the chord is chat's, elsewhere ctrl+x is nothing
+        assert!(matches!(
+            keymap.lookup(&[Context::Home], &[], &press("ctrl+x")),
+            Lookup::Unbound
+        ));
+    }
+
+    #[test]
+    fn a_key_in_front_of_a_chord() {
+        let keymap = with_file(
+            r#"
+            [chat]
+            "ctrl+x" = "send"
+            "#,
+        );
+        assert_eq!(
+            keymap.problems,
+            ["[chat] ctrl+x (send) hides ctrl+x ctrl+e (edit_externally)"]
+        );
+        assert!(keymap
+            .keys_for(&[Context::Chat], "edit_externally")
+            .is_empty());
+    }
+
+    #[test]
+    fn global_keys_against_a_contexts_chords() {
+        // a global key and chat's chord: chat goes first, so the chord wins there
+        let keymap = with_file(
+            r#"
+            [global]
+            "ctrl+x" = "quit"
+            "#,
+        );
+        assert_eq!(
+            keymap.problems,
+            ["[chat] ctrl+x ctrl+e (edit_externally) hides ctrl+x (quit, from global)"]
+        );
+        assert_eq!(keymap.keys_for(&[Context::Chat], "quit"), ["ctrl+c"]);
+        assert_eq!(
+            keymap.keys_for(&[Context::Home], "quit"),
+            ["ctrl+c", "ctrl+x"]
+        );
+
+        // a global chord and chat's key: chat's key wins
+        let keymap = with_file(
+            r#"
+            [global]
+            "f5 f6" = "help"
+            [home]
+            "f5" = "quit"
+            "#,
+        );
+        assert_eq!(
+            keymap.problems,
+            ["[home] f5 (quit) hides f5 f6 (help, from global)"]
+        );
+        assert_eq!(keymap.keys_for(&[Context::Home], "help"), ["?", "f1"]);
+        assert_eq!(keymap.keys_for(&[Context::Chat], "help"), ["f1", "f5 f6"]);
+    }
+
+    #[test]
+    fn overridden_global_keys_arent_offered() {
+        let keymap = with_file(
+            r#"
+            [global]
+            "ctrl+n" = "newline"
+            [chat]
+            "ctrl+n" = "send"
+            "#,
+        );
+        assert!(keymap.problems.is_empty());
+        assert_eq!(
+            keymap.keys_for(&[Context::Chat], "newline"),
+            ["ctrl+j", "alt+enter", "shift+enter"]
+        );
+        assert_eq!(
+            keymap.keys_for(&[Context::Chat], "send"),
+            ["enter", "ctrl+n"]
+        );
+        // help lists each context on its own
+        assert!(keymap
+            .describe(Context::Global)
+            .iter()
+            .any(|(_, keys)| keys == &["ctrl+n"]));
+    }
+}
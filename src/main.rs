@@ -1,101 +1,386 @@
+mod action;
 mod api;
 mod app;
 mod auth_tui;
+mod background;
 mod chat_tui;
+mod colors;
+mod commands;
+mod completion;
+mod config;
+mod diag;
+mod drafts;
+mod editor;
+mod emoji;
+mod emoji_picker;
+mod external_editor;
+mod glyph_picker;
+mod glyphs;
+mod help;
+mod history;
 mod home_tui;
+mod keymap;
+mod paste_prompt;
+mod profile;
+mod text_layout;
+mod theme;
+mod theme_picker;
+mod update;
+mod vim;
+use futures_util::StreamExt;
 use ratatui::crossterm::{
-    event::{self, EnableMouseCapture},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, EventStream, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{enable_raw_mode, EnterAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::collections::VecDeque;
 use std::io::{self};
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
 
+use action::{Action, AppEvent, Effect};
 use app::{App, Page};
+use config::Dirs;
+use glyphs::GlyphSet;
 
 // don't worry guys, im too lazy to write comments, also im trying to organize this lasagna code
 
+// nothing scheduled, sleep for a long while (waking up without a change doesn't draw)
+const IDLE_WAKEUP: Duration = Duration::from_secs(3600);
+
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
     enable_raw_mode()?;
-    let (chat_tx, chat_rx) = std::sync::mpsc::channel::<chat_tui::ChatMessage>();
     let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let app = Arc::new(Mutex::new(App::new()));
-    let (tx, _rx) = mpsc::unbounded_channel();
-    let (outgoing_tx, outgoing_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
-    let mut maybe_outgoing_rx = Some(outgoing_rx);
-    let mut ws_started = false;
-
-    let tick_rate = Duration::from_millis(100);
-    let mut last_tick = Instant::now();
-    let ws_url = "ws://isock.reetui.hackclub.app";
-
-    loop {
-        while let Ok(msg) = chat_rx.try_recv() {
-            let mut app_lock = app.lock().unwrap();
-            app_lock.chat_messages.push(msg);
-            if app_lock.chat_messages.len() > 1000 {
-                app_lock.chat_messages.remove(0);
-            }
-        }
+    let mut app = App::new();
+    let (detection, typed) = background::detect(terminal.backend_mut());
+    app.set_background(detection);
+    // keys pressed while the probe had stdin, they go first
+    let mut typeahead: VecDeque<Event> = typed.into_iter().map(Event::Key).collect();
+    pick_glyphs(&mut app, terminal.backend_mut());
+    app.keyboard_enhanced = enhance_keyboard(terminal.backend_mut());
+    terminal.clear()?;
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<AppEvent>();
+    let mut chat: Option<Connection> = None;
+    let saver = Saver::spawn();
+    let mut events = EventStream::new();
+    theme::spawn_watcher(event_tx.clone(), app.dirs.clone());
 
-        {
-            let app_lock = app.lock().unwrap();
-            if !ws_started {
-                if let Some(token) = app_lock.token.clone() {
-                    if let Some(rx) = maybe_outgoing_rx.take() {
-                        chat_tui::start_ws_thread(ws_url.to_string(), token, chat_tx.clone(), rx);
-                        ws_started = true;
-                    }
+    while !app.should_quit {
+        if app.dirty {
+            app.dirty = false;
+            let frame = terminal.draw(|f| {
+                match app.page {
+                    Page::Auth => auth_tui::ui(f, &mut app),
+                    Page::Home => home_tui::ui(f, &app),
+                    Page::Chat => chat_tui::ui(f, &mut app),
                 }
+                theme_picker::ui(f, &app);
+                glyph_picker::ui(f, &app);
+                emoji_picker::ui(f, &app);
+                paste_prompt::ui(f, &app);
+                help::ui(f, &mut app);
+                profile::ui(f, &app);
+                diag::ui(f, &app);
+            })?;
+            // links under a popup would make the popup clickable, so leave them out while it's up
+            let popup = app.theme_picker.is_some()
+                || app.glyph_picker.is_some()
+                || app.emoji_picker.is_some()
+                || app.pending_paste.is_some()
+                || app.completion.is_some()
+                || app.help.is_some()
+                || app.profile.is_some()
+                || app.show_diagnostics;
+            if matches!(app.page, Page::Chat) && app.hyperlinks_supported && !popup {
+                chat_tui::write_hyperlinks(&mut io::stdout(), frame.buffer, &app.hyperlinks)?;
             }
         }
 
-        {
-            let mut app_lock = app.lock().unwrap();
-            terminal.draw(|f| match app_lock.page {
-                Page::Auth => auth_tui::ui(f, &mut app_lock),
-                Page::Home => home_tui::ui(f, &app_lock),
-                Page::Chat => {
-                    let chat_messages = app_lock.chat_messages.clone();
-                    chat_tui::ui(f, &mut app_lock, &chat_messages);
+        let deadline = update::next_deadline(&app).unwrap_or_else(|| Instant::now() + IDLE_WAKEUP);
+        let actions = if let Some(evt) = typeahead.pop_front() {
+            update::map_event(&app, evt).into_iter().collect()
+        } else {
+            tokio::select! {
+            evt = events.next() => {
+                let Some(evt) = evt else { break };
+                update::map_event(&app, evt?).into_iter().collect()
+            }
+            Some(ev) = event_rx.recv() => {
+                let mut actions = vec![Action::Net(ev)];
+                while let Ok(ev) = event_rx.try_recv() {
+                    actions.push(Action::Net(ev));
                 }
-            })?;
-        }
+                actions
+            }
+            _ = tokio::time::sleep_until(deadline.into()) => vec![Action::Tick],
+            }
+        };
 
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
-        if event::poll(timeout)? {
-            let evt = event::read()?;
-            let mut app_lock = app.lock().unwrap();
-            match app_lock.page {
-                Page::Auth => auth_tui::handle_event(evt, &mut app_lock, &tx).await,
-                Page::Home => home_tui::handle_event(evt, &mut app_lock),
-                Page::Chat => {
-                    let input_width = app_lock.input_width;
-                    chat_tui::handle_event(evt, &mut app_lock, &outgoing_tx, input_width).await;
+        for action in actions {
+            for effect in update::update(&mut app, action) {
+                if let Effect::EditExternally(text) = effect {
+                    // the event reader thread would eat the editor's keystrokes, so it goes
+                    // away until the editor is done
+                    drop(events);
+                    let (result, restored) =
+                        suspended(&mut terminal, &app, || external_editor::edit(&text));
+                    events = EventStream::new();
+                    let _ = event_tx.send(AppEvent::EditorFinished(result));
+                    if let Err(e) = restored {
+                        let _ = event_tx.send(AppEvent::Error(format!(
+                            "Couldn't fully take the terminal back: {e}"
+                        )));
+                    }
+                    app.dirty = true;
+                    continue;
                 }
+                run_effect(effect, &app.dirs, &event_tx, &mut chat, &saver);
             }
         }
+    }
+
+    // whatever is still waiting on the save delay
+    app.sync_draft();
+    if app.drafts_changed.is_some() {
+        let (dirs, server, draft) = (
+            app.dirs.clone(),
+            app.history.server.clone(),
+            app.draft.clone(),
+        );
+        saver.save(&event_tx, move || drafts::save(&dirs, &server, &draft));
+    }
+    saver.finish();
+
+    if app.keyboard_enhanced {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+// terminals speaking the kitty keyboard protocol can tell Shift+Enter from Enter, which is
+// the only way to get it at all on most of them. None of the other flags, key releases would
+// just be noise
+fn enhance_keyboard(out: &mut impl io::Write) -> bool {
+    if !matches!(supports_keyboard_enhancement(), Ok(true)) {
+        return false;
+    }
+    execute!(
+        out,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+    )
+    .is_ok()
+}
+
+// hands the terminal back to the shell while `f` runs, then takes it again and redraws. every
+// step is tried whatever happened to the one before, a half restored terminal is worse than a
+// step that didn't take, and `f`'s result always comes back. the error is the first thing that
+// failed putting the terminal back
+fn suspended<T>(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &App,
+    f: impl FnOnce() -> T,
+) -> (T, io::Result<()>) {
+    if app.keyboard_enhanced {
+        let _ = execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags);
+    }
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    );
+    let _ = terminal.show_cursor();
+
+    let result = f();
+
+    let mut restored = vec![
+        enable_raw_mode(),
+        execute!(
+            terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableBracketedPaste
+        ),
+    ];
+    if app.mouse_captured {
+        restored.push(execute!(terminal.backend_mut(), EnableMouseCapture));
+    }
+    if app.keyboard_enhanced {
+        restored.push(execute!(
+            terminal.backend_mut(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        ));
+    }
+    restored.push(terminal.clear());
+    (result, restored.into_iter().collect())
+}
+
+// the config wins, then whatever we found out last time. on the very first run a terminal
+// that can't even do wide characters gets ascii, anything else gets asked
+fn pick_glyphs(app: &mut App, out: &mut impl io::Write) {
+    if let Some(set) = app.config.glyphs.or_else(|| glyphs::cached(&app.dirs)) {
+        app.set_glyphs(set);
+    } else if glyphs::probe_wide_chars(out) {
+        update::update(app, Action::OpenGlyphPicker);
+    } else {
+        app.set_glyphs(GlyphSet::Ascii);
+        let _ = glyphs::save_cache(&app.dirs, GlyphSet::Ascii);
+    }
+}
 
-        if last_tick.elapsed() >= tick_rate {
-            last_tick = Instant::now();
+// one thread doing the file saves off the ui thread, in the order they were asked for, so an
+// older snapshot of a file can't land after a newer one
+struct Saver {
+    tx: std::sync::mpsc::Sender<Box<dyn FnOnce() + Send>>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl Saver {
+    fn spawn() -> Self {
+        let (tx, rx) = std::sync::mpsc::channel::<Box<dyn FnOnce() + Send>>();
+        let thread = std::thread::spawn(move || {
+            for job in rx {
+                job();
+            }
+        });
+        Saver { tx, thread }
+    }
+
+    fn save(
+        &self,
+        events: &UnboundedSender<AppEvent>,
+        job: impl FnOnce() -> Result<(), String> + Send + 'static,
+    ) {
+        let events = events.clone();
+        let _ = self.tx.send(Box::new(move || {
+            if let Err(e) = job() {
+                let _ = events.send(AppEvent::Error(e));
+            }
+        }));
+    }
+
+    // waits for whatever is still queued
+    fn finish(self) {
+        drop(self.tx);
+        let _ = self.thread.join();
+    }
+}
+
+// the websocket task and the way to talk to it, dropping it hangs up
+struct Connection {
+    tx: UnboundedSender<String>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn run_effect(
+    effect: Effect,
+    dirs: &Dirs,
+    events: &UnboundedSender<AppEvent>,
+    chat: &mut Option<Connection>,
+    saver: &Saver,
+) {
+    match effect {
+        Effect::Login { username, password } => {
+            let events = events.clone();
+            tokio::spawn(async move {
+                let res = api::login(&username, &password, api::API_BASE).await;
+                let _ = events.send(AppEvent::AuthFinished(res.map(|t| t.token)));
+            });
         }
-        {
-            let mut app_lock = app.lock().unwrap();
-            if let Some(error_time) = app_lock.error_time {
-                if error_time.elapsed().as_secs() >= 3 {
-                    app_lock.error = None;
-                    app_lock.error_time = None;
-                }
+        Effect::Register {
+            username,
+            password,
+            icon,
+        } => {
+            let events = events.clone();
+            tokio::spawn(async move {
+                let res = api::register(&username, &password, &icon, api::API_BASE).await;
+                let _ = events.send(AppEvent::AuthFinished(res.map(|t| t.token)));
+            });
+        }
+        Effect::ConnectChat { token } => {
+            *chat = None;
+            let (tx, rx) = mpsc::unbounded_channel();
+            let task =
+                chat_tui::spawn_ws_task(chat_tui::WS_URL.to_string(), token, events.clone(), rx);
+            *chat = Some(Connection { tx, task });
+        }
+        Effect::Disconnect => *chat = None,
+        Effect::SendChat(msg) => {
+            if let Some(chat) = chat {
+                let _ = chat.tx.send(msg);
+            }
+        }
+        Effect::OpenUrl(url) => {
+            if let Err(e) = chat_tui::open_url(&url) {
+                let _ = events.send(AppEvent::Error(e));
+            }
+        }
+        Effect::SaveConfig(config) => {
+            if let Err(e) = config.save(dirs) {
+                let _ = events.send(AppEvent::Error(e));
+            }
+        }
+        Effect::SaveHistory { server, entries } => {
+            let dirs = dirs.clone();
+            saver.save(events, move || history::save(&dirs, &server, &entries));
+        }
+        Effect::SetMouseCapture(on) => {
+            let result = if on {
+                execute!(io::stdout(), EnableMouseCapture)
+            } else {
+                execute!(io::stdout(), DisableMouseCapture)
+            };
+            if let Err(e) = result {
+                let _ = events.send(AppEvent::Error(format!("Couldn't change the mouse: {e}")));
+            }
+        }
+        Effect::SaveDraft { server, draft } => {
+            let dirs = dirs.clone();
+            saver.save(events, move || drafts::save(&dirs, &server, &draft));
+        }
+        Effect::SaveRecentEmoji(recent) => {
+            let dirs = dirs.clone();
+            saver.save(events, move || emoji::save_recent(&dirs, &recent));
+        }
+        // main handles this one itself, it needs the terminal
+        Effect::EditExternally(_) => {}
+        Effect::SaveGlyphs(set) => {
+            if let Err(e) = glyphs::save_cache(dirs, set) {
+                let _ = events.send(AppEvent::Error(e));
             }
         }
     }
@@ -0,0 +1,143 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::action::{Action, Effect, PasteChoice};
+use crate::app::App;
+use crate::auth_tui::fixed_rect_in_center;
+
+// pastes bigger than this ask first, a log dump usually wants to be a code block
+const LARGE_PASTE_BYTES: usize = 2000;
+const LARGE_PASTE_LINES: usize = 20;
+
+// terminals send \r or \r\n for newlines in a paste
+pub fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+pub fn is_large(text: &str) -> bool {
+    text.len() > LARGE_PASTE_BYTES || text.lines().count() > LARGE_PASTE_LINES
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    let Event::Key(KeyEvent { code, .. }) = evt else {
+        return None;
+    };
+    let choice = match code {
+        KeyCode::Char('i') | KeyCode::Enter => PasteChoice::Insert,
+        KeyCode::Char('c') => PasteChoice::CodeBlock,
+        KeyCode::Esc | KeyCode::Char('n') => PasteChoice::Cancel,
+        _ => return None,
+    };
+    Some(Action::ResolvePaste(choice))
+}
+
+pub fn update(app: &mut App, choice: PasteChoice) -> Vec<Effect> {
+    let Some(text) = app.pending_paste.take() else {
+        return vec![];
+    };
+    match choice {
+        PasteChoice::Insert => app.composer.insert_str(&text),
+        // goes in the composer like anything else, so Enter still checks the length and the
+        // rest of what sending does
+        PasteChoice::CodeBlock => {
+            let cursor = app.composer.cursor();
+            let own_line = cursor == 0 || app.composer.text()[..cursor].ends_with('\n');
+            app.composer.insert_str(&format!(
+                "{}```\n{}\n```",
+                if own_line { "" } else { "\n" },
+                text.trim_end_matches('\n')
+            ));
+        }
+        PasteChoice::Cancel => {}
+    }
+    vec![]
+}
+
+pub fn ui(f: &mut Frame, app: &App) {
+    let Some(text) = &app.pending_paste else {
+        return;
+    };
+    let theme = &app.theme;
+    let area = fixed_rect_in_center(f.area(), 46, 8);
+    let key = |k: &'static str| Span::styled(k, theme.focused(theme.button_focus));
+    let label = |l: &'static str| Span::styled(l, Style::default().fg(theme.text));
+
+    let lines = vec![
+        Line::from(Span::styled(
+            format!(
+                "That's a big paste ({} lines, {} bytes).",
+                text.lines().count(),
+                text.len()
+            ),
+            Style::default().fg(theme.text),
+        )),
+        Line::raw(""),
+        Line::from(vec![key("c"), label(" wrap it in a code block")]),
+        Line::from(vec![key("i"), label(" put it in the composer")]),
+        Line::from(vec![
+            key("Esc"),
+            Span::styled(
+                " drop it",
+                Style::default()
+                    .fg(theme.hint)
+                    .add_modifier(Modifier::ITALIC),
+            ),
+        ]),
+    ];
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_set(app.glyphs().border)
+                    .border_style(Style::default().fg(theme.border_focus))
+                    .title(Span::styled(
+                        " Paste ",
+                        Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                    )),
+            ),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Page;
+    use crate::update::{tests::app, update};
+
+    #[test]
+    fn code_block_lands_in_the_composer() {
+        let mut app = app();
+        app.page = Page::Chat;
+        app.composer.set_text("look:");
+        app.pending_paste = Some("a\nb\n".to_string());
+        let effects = update(&mut app, Action::ResolvePaste(PasteChoice::CodeBlock));
+        assert!(effects.is_empty());
+        assert_eq!(app.composer.text(), "look:\n```\na\nb\n```");
+        assert!(app.pending_paste.is_none());
+    }
+
+    #[test]
+    fn code_block_goes_through_the_length_check() {
+        let mut app = app();
+        app.page = Page::Chat;
+        let big = "x".repeat(app.config.max_message_length());
+        app.pending_paste = Some(big);
+        update(&mut app, Action::ResolvePaste(PasteChoice::CodeBlock));
+        // the fences push it over the limit
+        assert!(update(&mut app, Action::SendMessage).is_empty());
+        assert!(app.error.is_some());
+        assert!(app.composer.text().starts_with("```\n"));
+    }
+}
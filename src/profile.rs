@@ -0,0 +1,97 @@
+use crossterm::event::{Event, MouseButton, MouseEventKind};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::action::Action;
+use crate::app::App;
+use crate::auth_tui::fixed_rect_in_center;
+use crate::chat_tui::relative_time;
+use crate::glyphs::display_icon;
+
+// clicking a name in the chat. the server has no profile endpoint, so it's what this client
+// has seen of them since it started
+pub struct Profile {
+    pub user: String,
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    match evt {
+        Event::Key(_) => Some(Action::CloseProfile),
+        Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+            Some(Action::CloseProfile)
+        }
+        _ => None,
+    }
+}
+
+pub fn ui(f: &mut Frame, app: &App) {
+    let Some(profile) = &app.profile else {
+        return;
+    };
+    let theme = &app.theme;
+    let theirs: Vec<_> = app
+        .chat_messages
+        .iter()
+        .filter(|m| !m.system && m.user == profile.user)
+        .collect();
+    let icon = theirs
+        .iter()
+        .rev()
+        .find_map(|m| m.icon.as_deref())
+        .map_or(app.glyphs().avatar, |icon| {
+            display_icon(icon, app.glyph_set)
+        });
+    let first = theirs.iter().find_map(|m| m.timestamp);
+    let last = theirs.iter().rev().find_map(|m| m.timestamp);
+    let when = |ts: Option<i64>| ts.map_or("-".to_string(), relative_time);
+
+    let row = |key: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{key:<12}"), Style::default().fg(theme.muted)),
+            Span::styled(value, Style::default().fg(theme.text)),
+        ])
+    };
+    let lines = vec![
+        Line::from(vec![
+            Span::styled(format!("{icon} "), Style::default().fg(theme.button_focus)),
+            Span::styled(
+                profile.user.clone(),
+                Style::default()
+                    .fg(theme.button_focus)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::raw(""),
+        row("Messages", theirs.len().to_string()),
+        row("First seen", when(first)),
+        row("Last seen", when(last)),
+        Line::raw(""),
+        Line::from(Span::styled(
+            "any key to close",
+            Style::default()
+                .fg(theme.hint)
+                .add_modifier(Modifier::ITALIC),
+        )),
+    ];
+
+    let area = fixed_rect_in_center(f.area(), 36, lines.len() as u16 + 2);
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Profile ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
@@ -0,0 +1,384 @@
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// one place for all the "how wide is this and where does it wrap" math, used by the chat view
+// and the composer so the cursor lands where the text actually is. everything works on
+// extended grapheme clusters, so 🧑‍💻, flags and combining marks count as one thing
+
+pub const TAB_WIDTH: usize = 4;
+
+// a wrapped row, `start..end` is the byte range of the source text shown on it. newlines and
+// the whitespace a soft wrap happened on are left out, `soft` tells if the row ends because it
+// ran out of room rather than on a newline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualLine {
+    pub start: usize,
+    pub end: usize,
+    pub width: usize,
+    pub soft: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+    pub lines: Vec<VisualLine>,
+    pub width: usize,
+}
+
+pub fn grapheme_width(g: &str, col: usize) -> usize {
+    match g {
+        "\t" => TAB_WIDTH - col % TAB_WIDTH,
+        "\n" | "\r\n" | "\r" => 0,
+        _ => UnicodeWidthStr::width(g),
+    }
+}
+
+// display width of `text` when it starts at column `col`
+pub fn str_width_from(text: &str, col: usize) -> usize {
+    text.graphemes(true)
+        .fold(col, |acc, g| acc + grapheme_width(g, acc))
+        - col
+}
+
+pub fn str_width(text: &str) -> usize {
+    str_width_from(text, 0)
+}
+
+// tabs become spaces up to the next tab stop, everything else is left alone
+pub fn expand_tabs(text: &str, col: usize) -> Cow<'_, str> {
+    if !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + TAB_WIDTH);
+    let mut acc = col;
+    for g in text.graphemes(true) {
+        let w = grapheme_width(g, acc);
+        if g == "\t" {
+            out.extend(std::iter::repeat_n(' ', w));
+        } else {
+            out.push_str(g);
+        }
+        acc += w;
+    }
+    Cow::Owned(out)
+}
+
+fn is_newline(g: &str) -> bool {
+    matches!(g, "\n" | "\r\n" | "\r")
+}
+
+fn is_space(g: &str) -> bool {
+    g.chars().all(char::is_whitespace)
+}
+
+enum Token {
+    Newline,
+    Space,
+    Word,
+}
+
+// words are runs of non whitespace graphemes, every whitespace grapheme is its own token
+fn tokens(text: &str) -> impl Iterator<Item = (Token, usize, usize)> + '_ {
+    let mut graphemes = text.grapheme_indices(true).peekable();
+    std::iter::from_fn(move || {
+        let (start, g) = graphemes.next()?;
+        if is_newline(g) {
+            return Some((Token::Newline, start, start + g.len()));
+        }
+        if is_space(g) {
+            return Some((Token::Space, start, start + g.len()));
+        }
+        let mut end = start + g.len();
+        while let Some(&(i, next)) = graphemes.peek() {
+            if is_space(next) {
+                break;
+            }
+            end = i + next.len();
+            graphemes.next();
+        }
+        Some((Token::Word, start, end))
+    })
+}
+
+impl TextLayout {
+    // word wrap `text` into rows of at most `width` columns, falling back to breaking inside a
+    // word when it doesn't fit on a row of its own
+    pub fn new(text: &str, width: usize) -> Self {
+        let width = width.max(1);
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        let mut col = 0;
+
+        let row = |start, end, width, soft| VisualLine {
+            start,
+            end,
+            width,
+            soft,
+        };
+
+        for (token, start, end) in tokens(text) {
+            match token {
+                Token::Newline => {
+                    lines.push(row(line_start, start, col, false));
+                    line_start = end;
+                    col = 0;
+                }
+                Token::Space => {
+                    let w = grapheme_width(&text[start..end], col);
+                    if col + w <= width || col == 0 {
+                        col += w;
+                    } else {
+                        // the space the line breaks on hangs off the end and isn't drawn
+                        lines.push(row(line_start, start, col, true));
+                        line_start = end;
+                        col = 0;
+                    }
+                }
+                Token::Word => {
+                    let w = str_width_from(&text[start..end], col);
+                    if col + w <= width {
+                        col += w;
+                    } else if w <= width {
+                        lines.push(row(line_start, start, col, true));
+                        line_start = start;
+                        col = w;
+                    } else {
+                        for (i, g) in text[start..end].grapheme_indices(true) {
+                            let gw = grapheme_width(g, col);
+                            if col + gw > width && col > 0 {
+                                lines.push(row(line_start, start + i, col, true));
+                                line_start = start + i;
+                                col = 0;
+                            }
+                            col += gw;
+                        }
+                    }
+                }
+            }
+        }
+        lines.push(row(line_start, text.len(), col, false));
+
+        Self { lines, width }
+    }
+
+    // which row the cursor is on, a cursor sitting exactly on a soft wrap belongs to the row
+    // below it
+    pub fn cursor_line(&self, cursor: usize) -> usize {
+        self.lines
+            .iter()
+            .rposition(|l| l.start <= cursor)
+            .unwrap_or(0)
+    }
+
+    pub fn cursor_position(&self, text: &str, cursor: usize) -> (usize, usize) {
+        let line = self.cursor_line(cursor);
+        let l = self.lines[line];
+        let upto = cursor.clamp(l.start, l.end);
+        (line, str_width(&text[l.start..upto]).min(self.width))
+    }
+
+    // the byte offset on `line` closest to column `col` without going past it
+    pub fn cursor_at(&self, text: &str, line: usize, col: usize) -> usize {
+        let Some(l) = self.lines.get(line) else {
+            return text.len();
+        };
+        let mut acc = 0;
+        for (i, g) in text[l.start..l.end].grapheme_indices(true) {
+            let gw = grapheme_width(g, acc);
+            if acc + gw > col {
+                return l.start + i;
+            }
+            acc += gw;
+        }
+        self.line_end(text, line)
+    }
+
+    pub fn line_start(&self, line: usize) -> usize {
+        self.lines.get(line).map_or(0, |l| l.start)
+    }
+
+    // where End should put the cursor, a row that was broken mid word has no gap after it so
+    // the cursor stops before its last grapheme instead of jumping to the next row
+    pub fn line_end(&self, text: &str, line: usize) -> usize {
+        let Some(l) = self.lines.get(line) else {
+            return text.len();
+        };
+        let next_starts_here = self.lines.get(line + 1).is_some_and(|n| n.start == l.end);
+        if l.soft && next_starts_here {
+            text[l.start..l.end]
+                .grapheme_indices(true)
+                .next_back()
+                .map_or(l.start, |(i, _)| l.start + i)
+        } else {
+            l.end
+        }
+    }
+}
+
+pub fn prev_grapheme(text: &str, cursor: usize) -> usize {
+    text[..cursor]
+        .grapheme_indices(true)
+        .next_back()
+        .map_or(0, |(i, _)| i)
+}
+
+pub fn next_grapheme(text: &str, cursor: usize) -> usize {
+    text[cursor..]
+        .graphemes(true)
+        .next()
+        .map_or(text.len(), |g| cursor + g.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one of each kind of thing that has bitten the width math before
+    const PIECES: &[&str] = &[
+        "a",
+        "b",
+        " ",
+        "\t",
+        "\n",
+        "\r\n",
+        "🧑‍💻",
+        "🇫🇷",
+        "e\u{301}",
+        "漢",
+    ];
+
+    // every string of up to `len` pieces
+    fn strings(len: usize) -> Vec<String> {
+        let mut all = vec![String::new()];
+        let mut last = vec![String::new()];
+        for _ in 0..len {
+            last = last
+                .iter()
+                .flat_map(|s| PIECES.iter().map(move |p| format!("{s}{p}")))
+                .collect();
+            all.extend(last.iter().cloned());
+        }
+        all
+    }
+
+    fn rows<'a>(text: &'a str, layout: &TextLayout) -> Vec<&'a str> {
+        layout.lines.iter().map(|l| &text[l.start..l.end]).collect()
+    }
+
+    #[test]
+    fn rows_fit_the_width() {
+        for text in strings(4) {
+            for width in 1..=6 {
+                let layout = TextLayout::new(&text, width);
+                for l in &layout.lines {
+                    let row = &text[l.start..l.end];
+                    assert_eq!(l.width, str_width(row), "{text:?} at {width}");
+                    // a single grapheme wider than the box has nowhere else to go
+                    assert!(
+                        l.width <= width || row.graphemes(true).count() == 1,
+                        "{text:?} at {width}: {row:?} is {} wide",
+                        l.width
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rows_cover_the_text() {
+        for text in strings(4) {
+            for width in 1..=6 {
+                let layout = TextLayout::new(&text, width);
+                let lines = &layout.lines;
+                assert_eq!(lines[0].start, 0, "{text:?} at {width}");
+                assert_eq!(
+                    lines[lines.len() - 1].end,
+                    text.len(),
+                    "{text:?} at {width}"
+                );
+                for pair in lines.windows(2) {
+                    let gap = &text[pair[0].end..pair[1].start];
+                    if pair[0].soft {
+                        // broken mid word, or on the one space that hangs off the end
+                        assert!(
+                            gap.is_empty() || (gap.graphemes(true).count() == 1 && is_space(gap)),
+                            "{text:?} at {width}: soft gap {gap:?}"
+                        );
+                    } else {
+                        assert!(is_newline(gap), "{text:?} at {width}: hard gap {gap:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for text in strings(4) {
+            for width in 1..=6 {
+                let layout = TextLayout::new(&text, width);
+                let boundaries = text
+                    .grapheme_indices(true)
+                    .map(|(i, _)| i)
+                    .chain([text.len()]);
+                for cursor in boundaries {
+                    let (line, col) = layout.cursor_position(&text, cursor);
+                    // too wide to fit, the column is clamped to keep the cursor in the box
+                    if layout.lines[line].width > width {
+                        continue;
+                    }
+                    assert_eq!(
+                        layout.cursor_at(&text, line, col),
+                        cursor,
+                        "{text:?} at {width}, cursor {cursor} at {line}:{col}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let text = "hello big world";
+        let layout = TextLayout::new(text, 9);
+        assert_eq!(rows(text, &layout), ["hello big", "world"]);
+        assert!(layout.lines[0].soft);
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        let text = "ab abcdefgh";
+        let layout = TextLayout::new(text, 2);
+        assert_eq!(rows(text, &layout), ["ab", "ab", "cd", "ef", "gh"]);
+    }
+
+    #[test]
+    fn hard_breaks() {
+        let text = "one\n\ntwo\r\nthree";
+        let layout = TextLayout::new(text, 20);
+        assert_eq!(rows(text, &layout), ["one", "", "two", "three"]);
+        assert!(layout.lines.iter().all(|l| !l.soft));
+    }
+
+    #[test]
+    fn graphemes_are_one_thing() {
+        assert_eq!(str_width("🧑‍💻"), 2);
+        assert_eq!(str_width("🇫🇷"), 2);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(next_grapheme("🧑‍💻x", 0), "🧑‍💻".len());
+        assert_eq!(prev_grapheme("xe\u{301}", 4), 1);
+        // the ZWJ sequence never gets split across rows
+        let text = "🧑‍💻🧑‍💻🧑‍💻";
+        let layout = TextLayout::new(text, 3);
+        assert_eq!(rows(text, &layout), ["🧑‍💻", "🧑‍💻", "🧑‍💻"]);
+    }
+
+    #[test]
+    fn tabs_go_to_the_next_stop() {
+        assert_eq!(str_width("\t"), TAB_WIDTH);
+        assert_eq!(str_width("ab\t"), TAB_WIDTH);
+        assert_eq!(str_width_from("\t", 1), TAB_WIDTH - 1);
+        assert_eq!(expand_tabs("a\tb", 0), "a   b");
+        assert_eq!(expand_tabs("\tb", 2), "  b");
+    }
+}
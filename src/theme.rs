@@ -0,0 +1,196 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::action::AppEvent;
+use crate::colors::{self, ColorDepth};
+use crate::config::Dirs;
+
+// the default look, also what fills in any key a user theme leaves out
+const DEFAULT_THEME: &str = include_str!("../theme.json");
+
+// the user's own theme.json in the config dir shows up under this name
+pub const CUSTOM: &str = "custom";
+
+// baked into the binary, same format as theme.json
+pub const PRESETS: &[(&str, &str)] = &[
+    ("dark", DEFAULT_THEME),
+    ("light", include_str!("../themes/light.json")),
+    ("solarized", include_str!("../themes/solarized.json")),
+    (
+        "high-contrast",
+        include_str!("../themes/high-contrast.json"),
+    ),
+    ("monochrome", include_str!("../themes/monochrome.json")),
+];
+
+// every color the ui draws with, nothing should reach for a hardcoded Color anymore
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub border: Color,
+    #[serde(deserialize_with = "color")]
+    pub border_focus: Color,
+    #[serde(deserialize_with = "color")]
+    pub button: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_focus: Color,
+    #[serde(deserialize_with = "color")]
+    pub error_bg: Color,
+    #[serde(deserialize_with = "color")]
+    pub error_fg: Color,
+    #[serde(deserialize_with = "color")]
+    pub text: Color,
+    #[serde(deserialize_with = "color")]
+    pub input_hover: Color,
+    // secondary text, borders of message bubbles, timestamps
+    #[serde(deserialize_with = "color")]
+    pub muted: Color,
+    // key hints and footers
+    #[serde(deserialize_with = "color")]
+    pub hint: Color,
+    #[serde(deserialize_with = "color")]
+    pub accent: Color,
+    #[serde(deserialize_with = "color")]
+    pub link: Color,
+    // what the colors above were squashed to, see with_depth
+    #[serde(skip)]
+    pub depth: ColorDepth,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgb(u8, u8, u8),
+    Name(String),
+}
+
+// [r, g, b], "#rrggbb", a terminal color name like "lightblue" or a 256 color index
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    match ColorValue::deserialize(deserializer)? {
+        ColorValue::Rgb(r, g, b) => Ok(Color::Rgb(r, g, b)),
+        ColorValue::Name(name) => Color::from_str(&name)
+            .map_err(|_| serde::de::Error::custom(format!("unknown color \"{name}\""))),
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        serde_json::from_str(DEFAULT_THEME).expect("built-in theme.json is valid")
+    }
+}
+
+impl Theme {
+    pub fn error(&self) -> Style {
+        if self.depth == ColorDepth::None {
+            return Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED);
+        }
+        Style::default()
+            .fg(self.error_fg)
+            .bg(self.error_bg)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    // whatever has focus, without colors reverse video is the only thing left to show it
+    pub fn focused(&self, color: Color) -> Style {
+        let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+        if self.depth == ColorDepth::None {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    }
+
+    // every color brought down to what the terminal can show
+    pub fn with_depth(mut self, depth: ColorDepth) -> Self {
+        for color in [
+            &mut self.border,
+            &mut self.border_focus,
+            &mut self.button,
+            &mut self.button_focus,
+            &mut self.error_bg,
+            &mut self.error_fg,
+            &mut self.text,
+            &mut self.input_hover,
+            &mut self.muted,
+            &mut self.hint,
+            &mut self.accent,
+            &mut self.link,
+        ] {
+            *color = colors::quantize(*color, depth);
+        }
+        self.depth = depth;
+        self
+    }
+
+    // a user theme only has to list the colors it changes
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        let mut merged: Value = serde_json::from_str(DEFAULT_THEME).expect("built-in theme");
+        let user: Value = serde_json::from_str(data).map_err(|e| format!("Invalid theme: {e}"))?;
+        let (Value::Object(base), Value::Object(user)) = (&mut merged, user) else {
+            return Err("Invalid theme: expected a JSON object".to_string());
+        };
+        base.extend(user);
+        serde_json::from_value(merged).map_err(|e| format!("Invalid theme: {e}"))
+    }
+}
+
+pub fn preset(name: &str) -> Option<Theme> {
+    PRESETS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, data)| Theme::from_json(data).expect("built-in themes are valid"))
+}
+
+// everything the picker and /theme can switch to
+pub fn names(has_custom: bool) -> Vec<&'static str> {
+    let mut names: Vec<&str> = PRESETS.iter().map(|(n, _)| *n).collect();
+    if has_custom {
+        names.push(CUSTOM);
+    }
+    names
+}
+
+// the user's theme.json, None when there isn't one, a broken one is an error the caller can show
+pub fn load_custom(dirs: &Dirs) -> Result<Option<Theme>, String> {
+    let Some(path) = dirs.config_file("theme.json").filter(|p| p.exists()) else {
+        return Ok(None);
+    };
+    let data = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Couldn't read {}: {e}", path.display()))?;
+    Theme::from_json(&data).map(Some)
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// polls the theme file and sends the custom theme whenever it changes (or goes away)
+pub fn spawn_watcher(events: UnboundedSender<AppEvent>, dirs: Dirs) {
+    let Some(path) = dirs.config_file("theme.json") else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut last = modified(&path);
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let now = modified(&path);
+            if now == last {
+                continue;
+            }
+            last = now;
+            let event = match load_custom(&dirs) {
+                Ok(theme) => AppEvent::CustomThemeLoaded(theme),
+                Err(e) => AppEvent::Error(e),
+            };
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+    });
+}
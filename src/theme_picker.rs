@@ -0,0 +1,125 @@
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    layout::Alignment,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::action::{Action, Effect};
+use crate::app::App;
+use crate::auth_tui::fixed_rect_in_center;
+use crate::theme;
+
+// F2 from any page (or /theme in the chat), moving around previews the theme right away,
+// Enter keeps it and Esc puts back whatever was showing before
+pub struct ThemePicker {
+    pub names: Vec<&'static str>,
+    pub selected: usize,
+    pub previous: String,
+}
+
+pub fn map_event(evt: Event) -> Option<Action> {
+    let Event::Key(KeyEvent { code, .. }) = evt else {
+        return None;
+    };
+    let action = match code {
+        KeyCode::Up | KeyCode::Left | KeyCode::BackTab | KeyCode::Char('k') => Action::PickerPrev,
+        KeyCode::Down | KeyCode::Right | KeyCode::Tab | KeyCode::Char('j') => Action::PickerNext,
+        KeyCode::Enter => Action::PickerConfirm,
+        KeyCode::Esc | KeyCode::F(2) | KeyCode::Char('q') => Action::PickerCancel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    if let Action::OpenThemePicker = action {
+        let names = theme::names(app.custom_theme.is_some());
+        let selected = names.iter().position(|n| *n == app.theme_name).unwrap_or(0);
+        app.theme_picker = Some(ThemePicker {
+            names,
+            selected,
+            previous: app.theme_name.clone(),
+        });
+        return vec![];
+    }
+    let Some(picker) = app.theme_picker.as_mut() else {
+        return vec![];
+    };
+    let len = picker.names.len();
+    match action {
+        Action::PickerPrev => picker.selected = (picker.selected + len - 1) % len,
+        Action::PickerNext => picker.selected = (picker.selected + 1) % len,
+        Action::PickerConfirm => {
+            app.theme_picker = None;
+            app.config.theme = Some(app.theme_name.clone());
+            return app.save_config();
+        }
+        Action::PickerCancel => {
+            let previous = std::mem::take(&mut picker.previous);
+            app.theme_picker = None;
+            let _ = app.apply_theme(&previous);
+            return vec![];
+        }
+        _ => return vec![],
+    }
+    let name = picker.names[picker.selected];
+    if let Err(e) = app.apply_theme(name) {
+        app.error = Some(e);
+        app.error_time = Some(std::time::Instant::now());
+    }
+    vec![]
+}
+
+// drawn on top of whatever page is showing
+pub fn ui(f: &mut Frame, app: &App) {
+    let Some(picker) = &app.theme_picker else {
+        return;
+    };
+    let theme = &app.theme;
+    let glyphs = app.glyphs();
+    let area = fixed_rect_in_center(f.area(), 30, picker.names.len() as u16 + 4);
+
+    let mut lines: Vec<Line> = picker
+        .names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if i == picker.selected {
+                Line::from(Span::styled(
+                    format!("> {name}"),
+                    theme.focused(theme.button_focus),
+                ))
+            } else {
+                Line::from(Span::styled(
+                    format!("  {name}"),
+                    Style::default().fg(theme.text),
+                ))
+            }
+        })
+        .collect();
+    lines.push(Line::raw(""));
+    lines.push(Line::from(Span::styled(
+        "Enter: keep  Esc: cancel",
+        Style::default()
+            .fg(theme.hint)
+            .add_modifier(Modifier::ITALIC),
+    )));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(glyphs.border)
+                .border_style(Style::default().fg(theme.border_focus))
+                .title(Span::styled(
+                    " Theme ",
+                    Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+                )),
+        ),
+        area,
+    );
+}
@@ -0,0 +1,518 @@
+use crossterm::event::{Event, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
+use std::time::{Duration, Instant};
+
+use crate::action::{Action, AppEvent, ClickTarget, Effect};
+use crate::app::{App, Page};
+use crate::{
+    auth_tui, chat_tui, emoji_picker, glyph_picker, help, home_tui, paste_prompt, profile, theme,
+    theme_picker,
+};
+
+// how long an error stays on screen
+pub const ERROR_TTL: Duration = Duration::from_secs(3);
+
+const DRAFT_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+// turns a terminal event into an action for whatever page is showing
+pub fn map_event(app: &App, evt: Event) -> Option<Action> {
+    if let Event::Resize(..) = evt {
+        return Some(Action::Resize);
+    }
+    // terminals that report releases would otherwise get every key twice
+    if let Event::Key(KeyEvent {
+        kind: KeyEventKind::Release,
+        ..
+    }) = evt
+    {
+        return None;
+    }
+    // an open popup gets every key
+    if app.show_diagnostics {
+        return matches!(evt, Event::Key(_)).then_some(Action::CloseDiagnostics);
+    }
+    if app.help.is_some() {
+        return help::map_event(evt);
+    }
+    if app.profile.is_some() {
+        return profile::map_event(evt);
+    }
+    if app.glyph_picker.is_some() {
+        return glyph_picker::map_event(evt);
+    }
+    if app.emoji_picker.is_some() {
+        return emoji_picker::map_event(evt);
+    }
+    if app.pending_paste.is_some() {
+        return paste_prompt::map_event(evt);
+    }
+    if app.theme_picker.is_some() {
+        return theme_picker::map_event(evt);
+    }
+    if let Event::Mouse(mouse) = evt {
+        return map_mouse(app, mouse);
+    }
+    match app.page {
+        Page::Auth => auth_tui::map_event(evt, app),
+        Page::Home => home_tui::map_event(evt, app),
+        Page::Chat => chat_tui::map_event(evt, app),
+    }
+}
+
+// with the mouse let go the terminal does its own text selection again, nothing's clickable
+pub fn toggle_mouse(app: &mut App) -> Vec<Effect> {
+    app.mouse_captured = !app.mouse_captured;
+    let key = app
+        .keymap
+        .keys_for(&[], "toggle_mouse")
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "/mouse".to_string());
+    app.error = Some(if app.mouse_captured {
+        format!("Mouse is back, {key} lets go of it again")
+    } else {
+        format!("Mouse released for selecting text, {key} takes it back")
+    });
+    app.error_time = Some(Instant::now());
+    vec![Effect::SetMouseCapture(app.mouse_captured)]
+}
+
+// lines per wheel notch
+const WHEEL_LINES: isize = 3;
+
+// the wheel scrolls the chat, a left click goes to whatever the page marked under it
+fn map_mouse(app: &App, mouse: MouseEvent) -> Option<Action> {
+    match mouse.kind {
+        MouseEventKind::ScrollUp if matches!(app.page, Page::Chat) => {
+            Some(Action::ScrollChat(-WHEEL_LINES))
+        }
+        MouseEventKind::ScrollDown if matches!(app.page, Page::Chat) => {
+            Some(Action::ScrollChat(WHEEL_LINES))
+        }
+        MouseEventKind::Down(MouseButton::Left) if matches!(app.page, Page::Home) => {
+            Some(Action::Continue)
+        }
+        MouseEventKind::Down(MouseButton::Left) => app
+            .click_areas
+            .iter()
+            .rev()
+            .find(|(area, _)| area.contains((mouse.column, mouse.row).into()))
+            .map(|(area, target)| {
+                Action::Click(match target {
+                    ClickTarget::Input(i, _) => ClickTarget::Input(*i, mouse.column - area.x),
+                    ClickTarget::Composer(..) => {
+                        ClickTarget::Composer(mouse.row - area.y, mouse.column - area.x)
+                    }
+                    target => target.clone(),
+                })
+            }),
+        _ => None,
+    }
+}
+
+// the one place state changes, no io in here, anything that needs the outside world comes
+// back as an Effect for main to run
+pub fn update(app: &mut App, action: Action) -> Vec<Effect> {
+    // a chord only lasts until the next key
+    match action {
+        Action::ChordPrefix(key) => app.pending_keys.push(key),
+        Action::Tick | Action::Net(_) | Action::Resize => {}
+        _ => app.pending_keys.clear(),
+    }
+    let mut effects = reduce(app, action);
+    if matches!(app.page, Page::Chat) {
+        app.sync_draft();
+    }
+    // drafts go to disk once typing stops for a bit, not on every key
+    if app
+        .drafts_changed
+        .is_some_and(|t| t.elapsed() >= DRAFT_SAVE_DELAY)
+    {
+        app.drafts_changed = None;
+        effects.push(Effect::SaveDraft {
+            server: app.history.server.clone(),
+            draft: app.draft.clone(),
+        });
+    }
+    effects
+}
+
+fn reduce(app: &mut App, action: Action) -> Vec<Effect> {
+    match action {
+        Action::Tick => {
+            tick(app);
+            return vec![];
+        }
+        Action::Quit => app.should_quit = true,
+        Action::Resize => {}
+        Action::ChordPrefix(_) => {}
+        Action::Unbound(why) => {
+            if !why.is_empty() {
+                app.error = Some(why);
+                app.error_time = Some(Instant::now());
+            }
+        }
+        Action::CloseProfile => app.profile = None,
+        Action::ToggleMouse => {
+            app.dirty = true;
+            return toggle_mouse(app);
+        }
+        Action::OpenDiagnostics => app.show_diagnostics = true,
+        Action::CloseDiagnostics => app.show_diagnostics = false,
+        action @ (Action::OpenHelp
+        | Action::CloseHelp
+        | Action::HelpType(_)
+        | Action::HelpErase
+        | Action::HelpScroll(_)) => {
+            app.dirty = true;
+            return help::update(app, action);
+        }
+        Action::ResolvePaste(choice) => {
+            app.dirty = true;
+            return paste_prompt::update(app, choice);
+        }
+        // messages keep coming in whatever page we're on
+        Action::Net(AppEvent::ChatMessage(msg)) => app.push_message(msg),
+        Action::Net(AppEvent::CustomThemeLoaded(theme)) => {
+            app.custom_theme = theme;
+            // only follow the file if it's what's showing, or what would show once it exists
+            let name = app.configured_theme();
+            let following = app.theme_picker.is_none()
+                && (app.theme_name == theme::CUSTOM || name == theme::CUSTOM);
+            if following {
+                let name = if app.custom_theme.is_some() || name != theme::CUSTOM {
+                    name
+                } else {
+                    "dark".to_string()
+                };
+                if let Err(e) = app.apply_theme(&name) {
+                    app.error = Some(e);
+                    app.error_time = Some(Instant::now());
+                }
+            }
+        }
+        action @ (Action::OpenGlyphPicker | Action::PickGlyphs(_)) => {
+            app.dirty = true;
+            return glyph_picker::update(app, action);
+        }
+        action @ (Action::OpenEmojiPicker(_)
+        | Action::PickerCategory(_)
+        | Action::PickerType(_)
+        | Action::PickerErase) => {
+            app.dirty = true;
+            return emoji_picker::update(app, action);
+        }
+        action @ (Action::OpenThemePicker
+        | Action::PickerPrev
+        | Action::PickerNext
+        | Action::PickerConfirm
+        | Action::PickerCancel) => {
+            app.dirty = true;
+            return if app.glyph_picker.is_some() {
+                glyph_picker::update(app, action)
+            } else if app.emoji_picker.is_some() {
+                emoji_picker::update(app, action)
+            } else {
+                theme_picker::update(app, action)
+            };
+        }
+        Action::Net(AppEvent::Error(e)) => {
+            app.error = Some(e);
+            app.error_time = Some(Instant::now());
+        }
+        action => {
+            app.dirty = true;
+            return match app.page {
+                Page::Auth => auth_tui::update(app, action),
+                Page::Home => home_tui::update(app, action),
+                Page::Chat => chat_tui::update(app, action),
+            };
+        }
+    }
+    app.dirty = true;
+    vec![]
+}
+
+// timers fired, only redraw if something on screen actually changed
+fn tick(app: &mut App) {
+    if app.error_time.is_some_and(|t| t.elapsed() >= ERROR_TTL) {
+        app.error = None;
+        app.error_time = None;
+        app.dirty = true;
+    }
+    if matches!(app.page, Page::Chat) && app.timestamp_refresh.is_some_and(|t| t <= Instant::now())
+    {
+        app.dirty = true;
+    }
+}
+
+// the next moment something on screen changes by itself
+pub fn next_deadline(app: &App) -> Option<Instant> {
+    let error_expiry = app.error_time.map(|t| t + ERROR_TTL);
+    let refresh = match app.page {
+        Page::Chat => app.timestamp_refresh,
+        _ => None,
+    };
+    let drafts_save = app.drafts_changed.map(|t| t + DRAFT_SAVE_DELAY);
+    [error_expiry, refresh, drafts_save]
+        .into_iter()
+        .flatten()
+        .min()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::app::AuthMode;
+    use crate::chat_tui::ChatMessage;
+    use crate::config::Dirs;
+    use crate::drafts::Draft;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    // an App with nowhere to keep files, so a test never reads or writes the real ones
+    pub fn app() -> App {
+        App::with_dirs(Dirs::default())
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            update(app, Action::InsertChar(c));
+        }
+    }
+
+    #[test]
+    fn submitting_the_login_form() {
+        let mut app = app();
+        update(&mut app, Action::SetAuthMode(AuthMode::Login));
+        type_text(&mut app, "ana");
+        update(&mut app, Action::FocusNext);
+        type_text(&mut app, "1234");
+        update(&mut app, Action::FocusNext);
+        let effects = update(&mut app, Action::Submit);
+        assert_eq!(
+            effects,
+            [Effect::Login {
+                username: "ana".to_string(),
+                password: "1234".to_string(),
+            }]
+        );
+        assert!(app.is_loading);
+        // a second Enter while it's in flight doesn't send it again
+        assert!(update(&mut app, Action::Submit).is_empty());
+    }
+
+    #[test]
+    fn login_needs_both_fields() {
+        let mut app = app();
+        update(&mut app, Action::SetAuthMode(AuthMode::Login));
+        type_text(&mut app, "ana");
+        update(&mut app, Action::FocusNext);
+        update(&mut app, Action::FocusNext);
+        assert!(update(&mut app, Action::Submit).is_empty());
+        assert!(app.error.is_some());
+        assert!(!app.is_loading);
+    }
+
+    #[test]
+    fn finished_auth_opens_home_and_connects() {
+        let mut app = app();
+        app.is_loading = true;
+        let effects = update(
+            &mut app,
+            Action::Net(AppEvent::AuthFinished(Ok("token".to_string()))),
+        );
+        assert!(matches!(app.page, Page::Home));
+        assert!(!app.is_loading);
+        assert_eq!(app.token.as_deref(), Some("token"));
+        assert_eq!(
+            effects,
+            [Effect::ConnectChat {
+                token: "token".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn failed_auth_stays_put() {
+        let mut app = app();
+        let effects = update(
+            &mut app,
+            Action::Net(AppEvent::AuthFinished(Err("401 Unauthorized".to_string()))),
+        );
+        assert!(effects.is_empty());
+        assert!(matches!(app.page, Page::Auth));
+        assert!(app.error.is_some());
+    }
+
+    #[test]
+    fn sending_a_message() {
+        let mut app = app();
+        app.page = Page::Chat;
+        type_text(&mut app, "hi :wave:");
+        let effects = update(&mut app, Action::SendMessage);
+        assert!(effects.contains(&Effect::SendChat("hi 👋".to_string())));
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            Effect::SaveHistory { entries, .. } if entries.last().map(String::as_str) == Some("hi :wave:")
+        )));
+        assert_eq!(app.composer.text(), "");
+        assert!(app.last_sent.is_some());
+
+        // too soon after the last one, nothing goes out and the text stays
+        type_text(&mut app, "again");
+        assert!(update(&mut app, Action::SendMessage).is_empty());
+        assert_eq!(app.composer.text(), "again");
+    }
+
+    #[test]
+    fn long_messages_stay_in_the_composer() {
+        let mut app = app();
+        app.page = Page::Chat;
+        let long = "a".repeat(app.config.max_message_length() + 1);
+        app.composer.set_text(&long);
+        assert!(update(&mut app, Action::SendMessage).is_empty());
+        assert!(app.error.is_some());
+        assert_eq!(app.composer.text(), long);
+    }
+
+    #[test]
+    fn scrolling_is_clamped() {
+        let mut app = app();
+        app.page = Page::Chat;
+        app.max_scroll = 10;
+        app.chat_scroll = 10;
+        app.auto_scroll = true;
+
+        update(&mut app, Action::ScrollChat(-3));
+        assert_eq!(app.chat_scroll, 7);
+        assert!(!app.auto_scroll);
+        update(&mut app, Action::ScrollChat(-100));
+        assert_eq!(app.chat_scroll, 0);
+        update(&mut app, Action::ScrollChat(100));
+        assert_eq!(app.chat_scroll, 10);
+        // back at the bottom follows new messages again
+        assert!(app.auto_scroll);
+    }
+
+    #[test]
+    fn messages_arrive_on_any_page() {
+        let mut app = app();
+        let msg = ChatMessage {
+            user: "bo".to_string(),
+            content: "yo".to_string(),
+            ..Default::default()
+        };
+        update(&mut app, Action::Net(AppEvent::ChatMessage(msg)));
+        assert_eq!(
+            app.chat_messages.back().map(|m| m.content.as_str()),
+            Some("yo")
+        );
+    }
+
+    #[test]
+    fn the_draft_follows_the_composer() {
+        let mut app = app();
+        app.page = Page::Chat;
+        type_text(&mut app, "hel");
+        assert_eq!(app.draft.text, "hel");
+        assert_eq!(app.draft.cursor, 3);
+        let first_change = app.drafts_changed;
+        assert!(first_change.is_some());
+        // more typing doesn't push the save back
+        type_text(&mut app, "lo");
+        assert_eq!(app.draft.text, "hello");
+        assert_eq!(app.drafts_changed, first_change);
+
+        // moving the cursor counts too
+        app.drafts_changed = None;
+        update(&mut app, Action::CursorLeft);
+        assert_eq!(app.draft.cursor, 4);
+        assert!(app.drafts_changed.is_some());
+
+        // nothing that touches the composer, nothing to save
+        app.drafts_changed = None;
+        update(&mut app, Action::ScrollChat(-1));
+        assert!(app.drafts_changed.is_none());
+    }
+
+    #[test]
+    fn the_draft_saves_once_typing_stops() {
+        let mut app = app();
+        app.page = Page::Chat;
+        type_text(&mut app, "hi");
+        assert!(update(&mut app, Action::Tick)
+            .iter()
+            .all(|e| !matches!(e, Effect::SaveDraft { .. })));
+        assert!(next_deadline(&app).is_some());
+
+        app.drafts_changed = Instant::now().checked_sub(DRAFT_SAVE_DELAY);
+        let effects = update(&mut app, Action::Tick);
+        assert_eq!(
+            effects,
+            [Effect::SaveDraft {
+                server: app.history.server.clone(),
+                draft: Draft {
+                    text: "hi".to_string(),
+                    cursor: 2,
+                },
+            }]
+        );
+        assert!(app.drafts_changed.is_none());
+        // and only the once
+        assert!(update(&mut app, Action::Tick).is_empty());
+    }
+
+    #[test]
+    fn sending_clears_the_draft() {
+        let mut app = app();
+        app.page = Page::Chat;
+        type_text(&mut app, "hi");
+        app.drafts_changed = None;
+        update(&mut app, Action::SendMessage);
+        assert_eq!(app.draft, Draft::default());
+        assert!(app.drafts_changed.is_some());
+    }
+
+    #[test]
+    fn a_message_can_start_with_a_question_mark() {
+        let mut app = app();
+        app.page = Page::Chat;
+        let evt = Event::Key(KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT));
+        let action = map_event(&app, evt).unwrap();
+        assert!(matches!(action, Action::InsertChar('?')));
+        update(&mut app, action);
+        assert_eq!(app.composer.text(), "?");
+        assert!(app.help.is_none());
+    }
+
+    #[test]
+    fn errors_expire_on_tick() {
+        let mut app = app();
+        app.error = Some("boom".to_string());
+        app.error_time = Some(Instant::now());
+        update(&mut app, Action::Tick);
+        assert!(app.error.is_some());
+
+        app.error_time = Instant::now().checked_sub(ERROR_TTL);
+        assert!(update(&mut app, Action::Tick).is_empty());
+        assert!(app.error.is_none());
+        assert!(app.error_time.is_none());
+    }
+
+    #[test]
+    fn a_broken_config_is_not_saved_over() {
+        let mut app = app();
+        app.page = Page::Chat;
+        app.composer.set_text("/theme light");
+        let effects = update(&mut app, Action::SendMessage);
+        assert!(effects.contains(&Effect::SaveConfig(app.config.clone())));
+
+        app.config_broken = true;
+        app.last_sent = None;
+        app.composer.set_text("/theme dark");
+        let effects = update(&mut app, Action::SendMessage);
+        assert!(!effects.iter().any(|e| matches!(e, Effect::SaveConfig(_))));
+        // the theme still changes, only the file is left alone
+        assert_eq!(app.theme_name, "dark");
+        assert!(app.error.is_some());
+    }
+}
@@ -0,0 +1,630 @@
+use crossterm::event::KeyCode;
+use std::ops::Range;
+
+use crate::editor::Editor;
+use crate::text_layout::{next_grapheme, prev_grapheme};
+
+// opt-in modal editing for the composer ("vim": true in config.json). it starts in insert so
+// the composer types like always, Esc drops to normal. everything goes through the editor so
+// undo and the kill ring are shared with the readline keys, p pastes what Ctrl+Y would
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Insert,
+    Normal,
+    Visual,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Insert => "INSERT",
+            Mode::Normal => "NORMAL",
+            Mode::Visual => "VISUAL",
+        }
+    }
+}
+
+pub struct Vim {
+    pub mode: Mode,
+    count: Option<usize>,
+    // d, c or y waiting for its motion, with the count typed before it
+    operator: Option<(char, usize)>,
+    // where v was pressed, the other end of the selection is the cursor
+    anchor: usize,
+    // the last yank or delete was whole lines, so p puts it on its own line
+    linewise: bool,
+}
+
+fn class(g: &str) -> u8 {
+    match g.chars().next() {
+        None => 0,
+        Some(c) if c.is_whitespace() => 0,
+        Some(c) if c.is_alphanumeric() || c == '_' => 1,
+        Some(_) => 2,
+    }
+}
+
+fn class_at(text: &str, pos: usize) -> u8 {
+    class(&text[pos..next_grapheme(text, pos)])
+}
+
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map_or(text.len(), |i| pos + i)
+}
+
+// w, start of the next word
+fn word_forward(text: &str, pos: usize) -> usize {
+    let mut pos = pos;
+    let start = class_at(text, pos);
+    if start != 0 {
+        while pos < text.len() && class_at(text, pos) == start {
+            pos = next_grapheme(text, pos);
+        }
+    }
+    while pos < text.len() && class_at(text, pos) == 0 {
+        pos = next_grapheme(text, pos);
+    }
+    pos
+}
+
+// b, start of this word or the one before
+fn word_back(text: &str, pos: usize) -> usize {
+    let mut pos = pos;
+    while pos > 0 && class_at(text, prev_grapheme(text, pos)) == 0 {
+        pos = prev_grapheme(text, pos);
+    }
+    if pos > 0 {
+        let class = class_at(text, prev_grapheme(text, pos));
+        while pos > 0 && class_at(text, prev_grapheme(text, pos)) == class {
+            pos = prev_grapheme(text, pos);
+        }
+    }
+    pos
+}
+
+// e, last grapheme of this word or the next one
+fn word_end(text: &str, pos: usize) -> usize {
+    let mut pos = next_grapheme(text, pos);
+    while pos < text.len() && class_at(text, pos) == 0 {
+        pos = next_grapheme(text, pos);
+    }
+    if pos >= text.len() {
+        return prev_grapheme(text, text.len());
+    }
+    let class = class_at(text, pos);
+    loop {
+        let next = next_grapheme(text, pos);
+        if next >= text.len() || class_at(text, next) != class {
+            return pos;
+        }
+        pos = next;
+    }
+}
+
+// j and k go by lines of the message, not wrapped rows, and keep the column in graphemes
+fn vertical(text: &str, pos: usize, down: bool, count: usize) -> usize {
+    let mut start = line_start(text, pos);
+    let mut col = 0;
+    let mut p = start;
+    while p < pos {
+        p = next_grapheme(text, p);
+        col += 1;
+    }
+    for _ in 0..count {
+        if down {
+            let end = line_end(text, start);
+            if end == text.len() {
+                break;
+            }
+            start = end + 1;
+        } else {
+            if start == 0 {
+                break;
+            }
+            start = line_start(text, start - 1);
+        }
+    }
+    let end = line_end(text, start);
+    let mut pos = start;
+    for _ in 0..col {
+        if pos >= end {
+            break;
+        }
+        pos = next_grapheme(text, pos);
+    }
+    pos
+}
+
+// where a motion key lands, and whether the grapheme it lands on is part of an operator's range
+fn motion(text: &str, pos: usize, key: KeyCode, count: usize) -> Option<(usize, bool)> {
+    let mut target = pos;
+    let inclusive = matches!(key, KeyCode::Char('e'));
+    for _ in 0..count {
+        target = match key {
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
+                if target == line_start(text, target) {
+                    target
+                } else {
+                    prev_grapheme(text, target)
+                }
+            }
+            KeyCode::Char('l') | KeyCode::Right => {
+                next_grapheme(text, target).min(line_end(text, target))
+            }
+            KeyCode::Char('w') => word_forward(text, target),
+            KeyCode::Char('b') => word_back(text, target),
+            KeyCode::Char('e') => word_end(text, target),
+            KeyCode::Char('0') | KeyCode::Home => line_start(text, target),
+            KeyCode::Char('$') | KeyCode::End => line_end(text, target),
+            _ => return None,
+        };
+    }
+    Some((target, inclusive))
+}
+
+// normal mode sits on a grapheme, not after the last one
+fn clamp(text: &str, pos: usize) -> usize {
+    if pos == line_end(text, pos) && pos > line_start(text, pos) {
+        prev_grapheme(text, pos)
+    } else {
+        pos
+    }
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Vim {
+            mode: Mode::Insert,
+            count: None,
+            operator: None,
+            anchor: 0,
+            linewise: false,
+        }
+    }
+
+    // keys vim doesn't take (Ctrl+U, history, a paste) still edit the text under a selection,
+    // so the anchor can point past the end or into the middle of something by now
+    fn anchor(&self, text: &str) -> usize {
+        if self.anchor >= text.len() {
+            return text.len();
+        }
+        let mut anchor = self.anchor;
+        while !text.is_char_boundary(anchor) {
+            anchor -= 1;
+        }
+        prev_grapheme(text, next_grapheme(text, anchor))
+    }
+
+    // the selected bytes in visual mode, the grapheme under the cursor included
+    pub fn selection(&self, editor: &Editor) -> Option<Range<usize>> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        let anchor = self.anchor(editor.text());
+        let start = anchor.min(editor.cursor());
+        let end = anchor.max(editor.cursor());
+        Some(start..next_grapheme(editor.text(), end))
+    }
+
+    // title of the composer box, with whatever is half typed
+    pub fn indicator(&self) -> String {
+        let mut pending = String::new();
+        if let Some((op, count)) = self.operator {
+            if count > 1 {
+                pending.push_str(&count.to_string());
+            }
+            pending.push(op);
+        }
+        if let Some(count) = self.count {
+            pending.push_str(&count.to_string());
+        }
+        if pending.is_empty() {
+            format!("-- {} --", self.mode.name())
+        } else {
+            format!("-- {} -- {}", self.mode.name(), pending)
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+    }
+
+    fn insert(&mut self, editor: &mut Editor, cursor: usize) {
+        self.reset();
+        self.mode = Mode::Insert;
+        editor.set_cursor(cursor);
+    }
+
+    pub fn key(&mut self, editor: &mut Editor, key: KeyCode) {
+        if key == KeyCode::Esc {
+            let text = editor.text();
+            let cursor = editor.cursor();
+            // leaving insert steps back onto the last thing typed, like vim does
+            if self.mode == Mode::Insert && cursor > line_start(text, cursor) {
+                editor.set_cursor(prev_grapheme(text, cursor));
+            }
+            self.reset();
+            self.mode = Mode::Normal;
+            return;
+        }
+        if let KeyCode::Char(c @ '0'..='9') = key {
+            if c != '0' || self.count.is_some() {
+                let digit = c as usize - '0' as usize;
+                self.count = Some(self.count.unwrap_or(0).saturating_mul(10) + digit);
+                return;
+            }
+        }
+        let count = self.count.take().unwrap_or(1).min(10_000);
+        match self.mode {
+            Mode::Visual => self.visual(editor, key, count),
+            _ => match self.operator.take() {
+                Some((op, op_count)) => self.operate(editor, op, key, count * op_count),
+                None => self.normal(editor, key, count),
+            },
+        }
+        if self.mode != Mode::Insert {
+            let cursor = clamp(editor.text(), editor.cursor());
+            editor.set_cursor(cursor);
+        }
+    }
+
+    fn normal(&mut self, editor: &mut Editor, key: KeyCode, count: usize) {
+        let text = editor.text();
+        let pos = editor.cursor();
+        if let Some((target, _)) = motion(text, pos, key, count) {
+            editor.set_cursor(target);
+            return;
+        }
+        match key {
+            KeyCode::Char('j') | KeyCode::Down => {
+                editor.set_cursor(vertical(text, pos, true, count))
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                editor.set_cursor(vertical(text, pos, false, count))
+            }
+            KeyCode::Char(op @ ('d' | 'c' | 'y')) => self.operator = Some((op, count)),
+            KeyCode::Char('i') => self.insert(editor, pos),
+            KeyCode::Char('a') => {
+                let cursor = next_grapheme(text, pos).min(line_end(text, pos));
+                self.insert(editor, cursor);
+            }
+            KeyCode::Char('I') => self.insert(editor, line_start(text, pos)),
+            KeyCode::Char('A') => self.insert(editor, line_end(text, pos)),
+            KeyCode::Char('o') => {
+                let end = line_end(text, pos);
+                self.insert(editor, end);
+                editor.insert_str("\n");
+            }
+            KeyCode::Char('O') => {
+                let start = line_start(text, pos);
+                self.insert(editor, start);
+                editor.insert_str("\n");
+                editor.set_cursor(start);
+            }
+            KeyCode::Char('x') => self.operate(editor, 'd', KeyCode::Char('l'), count),
+            KeyCode::Char('X') => self.operate(editor, 'd', KeyCode::Char('h'), count),
+            KeyCode::Char('s') => self.operate(editor, 'c', KeyCode::Char('l'), count),
+            KeyCode::Char('D') => self.operate(editor, 'd', KeyCode::Char('$'), 1),
+            KeyCode::Char('C') => self.operate(editor, 'c', KeyCode::Char('$'), 1),
+            KeyCode::Char('Y') => self.operate(editor, 'y', KeyCode::Char('y'), count),
+            KeyCode::Char('p') => self.paste(editor, true, count),
+            KeyCode::Char('P') => self.paste(editor, false, count),
+            KeyCode::Char('u') => {
+                for _ in 0..count {
+                    editor.undo();
+                }
+            }
+            KeyCode::Char('v') => {
+                self.anchor = pos;
+                self.mode = Mode::Visual;
+            }
+            _ => {}
+        }
+    }
+
+    // an operator and the key after it, dd/cc/yy (and j/k) work on whole lines
+    fn operate(&mut self, editor: &mut Editor, op: char, key: KeyCode, count: usize) {
+        let text = editor.text();
+        let pos = editor.cursor();
+        let lines = match key {
+            KeyCode::Char(c) if c == op => Some((pos, count - 1, true)),
+            KeyCode::Char('j') | KeyCode::Down => Some((pos, count, true)),
+            KeyCode::Char('k') | KeyCode::Up => Some((pos, count, false)),
+            _ => None,
+        };
+        if let Some((pos, extra, down)) = lines {
+            let other = vertical(text, pos, down, extra);
+            let start = line_start(text, pos.min(other));
+            let end = line_end(text, pos.max(other));
+            self.lines(editor, op, start..end);
+            return;
+        }
+        // cw changes to the end of the word like ce, the space after it stays
+        let key = match key {
+            KeyCode::Char('w') if op == 'c' && class_at(text, pos) != 0 => KeyCode::Char('e'),
+            key => key,
+        };
+        let Some((target, inclusive)) = motion(text, pos, key, count) else {
+            return;
+        };
+        let range = if target >= pos {
+            pos..if inclusive {
+                next_grapheme(text, target)
+            } else {
+                target
+            }
+        } else {
+            target..pos
+        };
+        self.apply(editor, op, range);
+    }
+
+    // whole lines, a delete takes a newline with them so no empty line is left behind
+    fn lines(&mut self, editor: &mut Editor, op: char, range: Range<usize>) {
+        let text = editor.text();
+        let yanked = format!("{}\n", &text[range.clone()]);
+        let start = range.start;
+        let cut = if op == 'c' {
+            range
+        } else if range.end < text.len() {
+            range.start..range.end + 1
+        } else {
+            range.start.saturating_sub(1)..range.end
+        };
+        editor.copy(yanked);
+        self.linewise = true;
+        match op {
+            'y' => editor.set_cursor(start),
+            'c' => {
+                editor.replace_range(cut, "");
+                self.mode = Mode::Insert;
+            }
+            _ => {
+                editor.replace_range(cut.clone(), "");
+                let text = editor.text();
+                let cursor = line_start(text, cut.start.min(text.len()));
+                editor.set_cursor(cursor);
+            }
+        }
+    }
+
+    fn apply(&mut self, editor: &mut Editor, op: char, range: Range<usize>) {
+        if range.is_empty() {
+            if op == 'c' {
+                self.mode = Mode::Insert;
+            }
+            return;
+        }
+        editor.copy(editor.text()[range.clone()].to_string());
+        self.linewise = false;
+        match op {
+            'y' => editor.set_cursor(range.start),
+            'c' => {
+                editor.replace_range(range, "");
+                self.mode = Mode::Insert;
+            }
+            _ => editor.replace_range(range, ""),
+        }
+    }
+
+    // p puts it after the cursor (or below the line), P before
+    fn paste(&mut self, editor: &mut Editor, after: bool, count: usize) {
+        let Some(register) = editor.last_kill() else {
+            return;
+        };
+        let text = editor.text();
+        let pos = editor.cursor();
+        if self.linewise {
+            let lines = register.trim_end_matches('\n').to_string() + "\n";
+            let lines = lines.repeat(count);
+            if !after {
+                let start = line_start(text, pos);
+                editor.set_cursor(start);
+                editor.insert_str(&lines);
+                editor.set_cursor(start);
+            } else {
+                let end = line_end(text, pos);
+                let lines = format!("\n{}", lines.trim_end_matches('\n'));
+                editor.set_cursor(end);
+                editor.insert_str(&lines);
+                editor.set_cursor(end + 1);
+            }
+            return;
+        }
+        let register = register.repeat(count);
+        let at = if after && pos < line_end(text, pos) {
+            next_grapheme(text, pos)
+        } else {
+            pos
+        };
+        editor.set_cursor(at);
+        editor.insert_str(&register);
+        // the cursor ends on the last pasted grapheme
+        let end = prev_grapheme(editor.text(), editor.cursor());
+        editor.set_cursor(end);
+    }
+
+    fn visual(&mut self, editor: &mut Editor, key: KeyCode, count: usize) {
+        let text = editor.text();
+        let pos = editor.cursor();
+        if let Some((target, _)) = motion(text, pos, key, count) {
+            editor.set_cursor(target);
+            return;
+        }
+        let Some(selection) = self.selection(editor) else {
+            return;
+        };
+        match key {
+            KeyCode::Char('j') | KeyCode::Down => {
+                editor.set_cursor(vertical(text, pos, true, count))
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                editor.set_cursor(vertical(text, pos, false, count))
+            }
+            KeyCode::Char('o') => {
+                editor.set_cursor(self.anchor(text));
+                self.anchor = pos;
+            }
+            KeyCode::Char(op @ ('d' | 'x' | 'c' | 's' | 'y')) => {
+                self.mode = Mode::Normal;
+                let op = match op {
+                    'x' => 'd',
+                    's' => 'c',
+                    op => op,
+                };
+                self.apply(editor, op, selection);
+            }
+            KeyCode::Char('v') => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // starts in normal mode with the cursor at `cursor`, \x1b is Esc
+    fn run(text: &str, cursor: usize, keys: &str) -> (Vim, Editor) {
+        let mut editor = Editor::default();
+        editor.set_text(text);
+        editor.set_cursor(cursor);
+        let mut vim = Vim::new();
+        vim.mode = Mode::Normal;
+        for c in keys.chars() {
+            let key = if c == '\x1b' {
+                KeyCode::Esc
+            } else {
+                KeyCode::Char(c)
+            };
+            vim.key(&mut editor, key);
+        }
+        (vim, editor)
+    }
+
+    fn cursor_after(text: &str, cursor: usize, keys: &str) -> usize {
+        run(text, cursor, keys).1.cursor()
+    }
+
+    fn text_after(text: &str, cursor: usize, keys: &str) -> String {
+        run(text, cursor, keys).1.text().to_string()
+    }
+
+    #[test]
+    fn word_motions() {
+        let text = "foo bar.baz  qux";
+        assert_eq!(cursor_after(text, 0, "w"), 4);
+        assert_eq!(cursor_after(text, 0, "ww"), 7);
+        assert_eq!(cursor_after(text, 0, "3w"), 8);
+        assert_eq!(cursor_after(text, 0, "e"), 2);
+        assert_eq!(cursor_after(text, 13, "b"), 8);
+        assert_eq!(cursor_after(text, 13, "2b"), 7);
+    }
+
+    #[test]
+    fn line_motions_stay_on_a_grapheme() {
+        let text = "ab\ncdé";
+        assert_eq!(cursor_after(text, 0, "$"), 1);
+        assert_eq!(cursor_after(text, 1, "j"), 4);
+        assert_eq!(cursor_after(text, 4, "$"), 5);
+        assert_eq!(cursor_after(text, 5, "0"), 3);
+        assert_eq!(cursor_after(text, 5, "k"), 1);
+        // l stops on the last grapheme of the line
+        assert_eq!(cursor_after(text, 0, "5l"), 1);
+        assert_eq!(cursor_after(text, 1, "h"), 0);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(text_after("foo bar baz", 0, "dw"), "bar baz");
+        assert_eq!(text_after("foo bar baz", 0, "d2w"), "baz");
+        assert_eq!(text_after("foo bar baz", 0, "2dw"), "baz");
+        assert_eq!(text_after("foo bar baz", 4, "D"), "foo ");
+        assert_eq!(text_after("foo bar", 0, "x"), "oo bar");
+        assert_eq!(text_after("foo bar", 0, "de"), " bar");
+        // cw stops at the end of the word like ce
+        let (vim, editor) = run("foo bar", 0, "cw");
+        assert_eq!(editor.text(), " bar");
+        assert_eq!(vim.mode, Mode::Insert);
+    }
+
+    #[test]
+    fn line_operators() {
+        assert_eq!(text_after("one\ntwo\nthree", 4, "dd"), "one\nthree");
+        assert_eq!(text_after("one\ntwo\nthree", 8, "dd"), "one\ntwo");
+        assert_eq!(text_after("one\ntwo\nthree", 0, "dj"), "three");
+        assert_eq!(text_after("one\ntwo", 0, "yyp"), "one\none\ntwo");
+        assert_eq!(text_after("one\ntwo", 4, "yyP"), "one\ntwo\ntwo");
+    }
+
+    #[test]
+    fn yank_and_paste() {
+        assert_eq!(text_after("ab", 0, "ylp"), "aab");
+        assert_eq!(text_after("foo bar", 0, "yw$p"), "foo barfoo ");
+        assert_eq!(text_after("ab", 1, "x0P"), "ba");
+    }
+
+    #[test]
+    fn undo_and_counts() {
+        assert_eq!(text_after("abc", 0, "xxu"), "bc");
+        assert_eq!(text_after("abcdef", 0, "3x"), "def");
+        let (vim, _) = run("abc", 0, "2d");
+        assert_eq!(vim.indicator(), "-- NORMAL -- 2d");
+    }
+
+    #[test]
+    fn visual_selection() {
+        let (vim, editor) = run("hello world", 0, "vw");
+        assert_eq!(vim.mode, Mode::Visual);
+        assert_eq!(vim.selection(&editor), Some(0..7));
+        // the selection is the same either way round
+        let (vim, editor) = run("hello world", 6, "vb");
+        assert_eq!(vim.selection(&editor), Some(0..7));
+        assert_eq!(text_after("hello world", 0, "vld"), "llo world");
+        assert_eq!(text_after("hello world", 6, "vey0P"), "worldhello world");
+        let (vim, editor) = run("hello", 0, "vlo");
+        assert_eq!(editor.cursor(), 0);
+        assert_eq!(vim.selection(&editor), Some(0..2));
+        assert_eq!(run("hello", 0, "vv").0.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn the_anchor_survives_edits_from_outside() {
+        // Esc, v, h then Ctrl+U: the text shrinks under the anchor
+        let (mut vim, mut editor) = run("hello", 4, "vh");
+        editor.kill_to_start();
+        assert_eq!(editor.text(), "lo");
+        assert_eq!(vim.selection(&editor), Some(0..2));
+        vim.key(&mut editor, KeyCode::Char('d'));
+        assert_eq!(editor.text(), "");
+
+        // and one that leaves it in the middle of a multi byte character
+        let (vim, mut editor) = run("aé", 1, "v");
+        editor.replace_range(0..3, "éa");
+        assert_eq!(vim.selection(&editor), Some(0..3));
+    }
+
+    #[test]
+    fn ctrl_u_in_visual_mode() {
+        use crate::action::Action;
+        use crate::app::Page;
+        use crate::update::{tests::app, update};
+
+        let mut app = app();
+        app.page = Page::Chat;
+        app.vim = Some(Vim::new());
+        app.composer.set_text("hello");
+        for key in [KeyCode::Esc, KeyCode::Char('v'), KeyCode::Char('h')] {
+            update(&mut app, Action::VimKey(key));
+        }
+        update(&mut app, Action::KillToStart);
+        let vim = app.vim.as_ref().expect("vim is on");
+        let selection = vim.selection(&app.composer).expect("still visual");
+        assert!(selection.end <= app.composer.text().len());
+    }
+}